@@ -0,0 +1,229 @@
+use crate::games::Game;
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+
+/// A single virtual function slot in the vtable of a libER class.
+pub(crate) struct Slot {
+    /// Name of the slot, which is the field name in the generated vtable.
+    pub name: &'static str,
+    /// Type of the slot for the given class, i.e. `extern "C" fn(&Class, ...) -> ...`.
+    pub signature: fn(&Ident) -> TokenStream,
+    /// Function that fills the slot for the given class. This is the shim of the library, which calls the trait
+    /// method and catches panics in it.
+    pub init: fn(&Ident) -> TokenStream,
+}
+
+/// A libER class that can be inherited with `#[derive(Inherit)]`.
+pub(crate) struct BaseClass {
+    /// Name of the C++ class, i.e. `FD4TaskBase`.
+    pub name: &'static str,
+    /// The game the layout is the one of. liber-rs has to be built for the same game.
+    pub game: Game,
+    /// Path to the `CppClass` type alias of the base class.
+    pub class_path: TokenStream,
+    /// Path to the data type of the base class. This must be the first field of the inheriting type.
+    pub type_path: TokenStream,
+    /// Paths to the data types of all the bases of this class, which the inheriting class inherits as well.
+    pub ancestors: Vec<TokenStream>,
+    /// Every slot of the vtable in order, including the slots inherited from the bases of this class.
+    pub slots: Vec<Slot>,
+    /// Paths to the generic vtable types of this class and all of its bases, from the root base to this class.
+    /// The generated vtable is laid out like every one of them.
+    pub vtables: Vec<TokenStream>,
+    /// If the class derives from `FD4ComponentBase`, and thus needs a `DLRuntimeClass` and a destructor.
+    pub component: bool,
+    /// Trait the inheriting class must implement itself, if any.
+    pub user_trait: Option<TokenStream>,
+    /// Implementations the base class declares `final`, so they can not be overridden by the inheritor.
+    pub final_impls: fn(&Ident) -> TokenStream,
+}
+
+/// Get the layout of a libER base class by name, in the game selected with the features of the crate. Only the
+/// last segment of a path is considered, so `FD4::FD4TaskBase` and `FD4TaskBase` are the same class.
+pub(crate) fn base_class(name: &str) -> Option<BaseClass> {
+    base_class_for(Game::SELECTED, name)
+}
+
+/// Get the layout of a libER base class in `game`. The layouts are the ones of the libER headers, which are
+/// written against ELDEN RING. No differences in these classes are known for the other games yet, so they share
+/// the slots until one is found.
+pub(crate) fn base_class_for(game: Game, name: &str) -> Option<BaseClass> {
+    let base = match name.rsplit("::").next().unwrap_or(name).trim() {
+        "DLRuntimeClass" => BaseClass {
+            name: "DLRuntimeClass",
+            game,
+            class_path: quote!(liber_rs::from::DLRF::DLRuntimeClass),
+            type_path: quote!(liber_rs::from::DLRF::DLRuntimeClassType),
+            ancestors: vec![],
+            slots: dl_runtime_class_slots(),
+            vtables: vec![quote!(liber_rs::from::DLRF::DLRuntimeClassVTable)],
+            component: false,
+            user_trait: Some(quote!(liber_rs::from::DLRF::DLRuntimeClassTrait)),
+            final_impls: |_| TokenStream::new(),
+        },
+        "FD4ComponentBase" => BaseClass {
+            name: "FD4ComponentBase",
+            game,
+            class_path: quote!(liber_rs::from::FD4::FD4ComponentBase),
+            type_path: quote!(liber_rs::from::FD4::FD4ComponentBaseType),
+            ancestors: vec![],
+            slots: fd4_component_base_slots(),
+            vtables: vec![quote!(liber_rs::from::FD4::FD4ComponentBaseVTable)],
+            component: true,
+            user_trait: None,
+            final_impls: |_| TokenStream::new(),
+        },
+        "FD4TaskBase" => BaseClass {
+            name: "FD4TaskBase",
+            game,
+            class_path: quote!(liber_rs::from::FD4::FD4TaskBase),
+            type_path: quote!(liber_rs::from::FD4::FD4TaskBaseType),
+            ancestors: vec![quote!(liber_rs::from::FD4::FD4ComponentBaseType)],
+            slots: fd4_task_base_slots(),
+            vtables: vec![
+                quote!(liber_rs::from::FD4::FD4ComponentBaseVTable),
+                quote!(liber_rs::from::FD4::FD4TaskBaseVTable),
+            ],
+            component: true,
+            user_trait: Some(quote!(liber_rs::from::FD4::FD4TaskBaseTrait)),
+            final_impls: |_| TokenStream::new(),
+        },
+        "CSEzTask" => BaseClass {
+            name: "CSEzTask",
+            game,
+            class_path: quote!(liber_rs::from::CS::CSEzTask),
+            type_path: quote!(liber_rs::from::CS::CSEzTaskType),
+            ancestors: vec![
+                quote!(liber_rs::from::FD4::FD4ComponentBaseType),
+                quote!(liber_rs::from::FD4::FD4TaskBaseType),
+            ],
+            slots: cs_ez_task_slots(),
+            vtables: vec![
+                quote!(liber_rs::from::FD4::FD4ComponentBaseVTable),
+                quote!(liber_rs::from::FD4::FD4TaskBaseVTable),
+                quote!(liber_rs::from::CS::CSEzTaskVTable),
+            ],
+            component: true,
+            user_trait: Some(quote!(liber_rs::from::CS::CSEzTaskTrait)),
+            // `FD4TaskBaseTrait::execute` is overridden by `CSEzTask` and declared `final`.
+            final_impls: |class| {
+                quote! {
+                    impl liber_rs::from::FD4::FD4TaskBaseTrait for #class {
+                        fn execute(&self, data: &liber_rs::from::FD4::FD4TaskData) {
+                            liber_rs::from::CS::CSEzTaskVirtual::virtual_eztask_execute(&self.0, data)
+                        }
+                    }
+                }
+            },
+        },
+        _ => return None,
+    };
+
+    Some(base)
+}
+
+/// Names of all the classes that can be inherited, for error messages.
+pub(crate) const BASE_CLASS_NAMES: &str = "DLRuntimeClass, FD4ComponentBase, FD4TaskBase, CSEzTask";
+
+fn dl_runtime_class_slots() -> Vec<Slot> {
+    vec![
+        Slot {
+            name: "class_name",
+            signature: |c| quote!(extern "C" fn(&#c) -> &'static core::ffi::c_char),
+            init: |c| quote!(liber_rs::from::DLRF::class_name_shim::<#c>),
+        },
+        Slot {
+            name: "class_name_w",
+            signature: |c| quote!(extern "C" fn(&#c) -> &'static u16),
+            init: |c| quote!(liber_rs::from::DLRF::class_name_w_shim::<#c>),
+        },
+        Slot {
+            name: "ref_byte1",
+            signature: |c| quote!(extern "C" fn(&#c) -> &core::sync::atomic::AtomicU8),
+            init: |c| quote!(liber_rs::from::DLRF::ref_byte1_shim::<#c>),
+        },
+        Slot {
+            name: "ref_byte2",
+            signature: |c| quote!(extern "C" fn(&#c) -> &core::sync::atomic::AtomicU8),
+            init: |c| quote!(liber_rs::from::DLRF::ref_byte2_shim::<#c>),
+        },
+        Slot {
+            name: "ref_byte3",
+            signature: |c| quote!(extern "C" fn(&#c) -> &core::sync::atomic::AtomicU8),
+            init: |c| quote!(liber_rs::from::DLRF::ref_byte3_shim::<#c>),
+        },
+        Slot {
+            name: "ref_byte4",
+            signature: |c| quote!(extern "C" fn(&#c) -> &core::sync::atomic::AtomicU8),
+            init: |c| quote!(liber_rs::from::DLRF::ref_byte4_shim::<#c>),
+        },
+        Slot {
+            name: "unk_always_false",
+            signature: |c| quote!(extern "C" fn(&#c) -> bool),
+            init: |c| quote!(liber_rs::from::DLRF::unk_always_false_shim::<#c>),
+        },
+        Slot {
+            name: "free_base",
+            signature: |c| {
+                quote!(unsafe extern "C" fn(
+                    &#c,
+                    &mut *mut liber_rs::from::FD4::FD4ComponentBase,
+                    &liber_rs::from::DLKR::DLAllocator,
+                ))
+            },
+            init: |c| quote!(liber_rs::from::DLRF::free_base_shim::<#c>),
+        },
+        Slot {
+            name: "class_size",
+            signature: |c| quote!(extern "C" fn(&#c) -> usize),
+            init: |c| quote!(liber_rs::from::DLRF::class_size_shim::<#c>),
+        },
+    ]
+}
+
+fn fd4_component_base_slots() -> Vec<Slot> {
+    vec![
+        Slot {
+            name: "get_runtime_class",
+            signature: |c| quote!(extern "C" fn(&#c) -> &'static liber_rs::from::DLRF::DLRuntimeClass),
+            init: |c| quote!(liber_rs::from::FD4::get_runtime_class_shim::<#c>),
+        },
+        Slot {
+            name: "destructor",
+            signature: |c| quote!(unsafe extern "C" fn(*mut #c, u32) -> *mut #c),
+            init: |c| quote!(liber_rs::from::FD4::component_destructor::<#c>),
+        },
+    ]
+}
+
+fn fd4_task_base_slots() -> Vec<Slot> {
+    let mut slots = fd4_component_base_slots();
+    slots.push(Slot {
+        name: "execute",
+        signature: |c| quote!(extern "C" fn(&#c, &liber_rs::from::FD4::FD4TaskData)),
+        init: |c| quote!(liber_rs::from::FD4::execute_shim::<#c>),
+    });
+    slots
+}
+
+fn cs_ez_task_slots() -> Vec<Slot> {
+    let mut slots = fd4_task_base_slots();
+    slots.extend([
+        Slot {
+            name: "eztask_execute",
+            signature: |c| quote!(extern "C" fn(&#c, &liber_rs::from::FD4::FD4TaskData)),
+            init: |c| quote!(liber_rs::from::CS::eztask_execute_shim::<#c>),
+        },
+        Slot {
+            name: "register_task",
+            signature: |c| quote!(extern "C" fn(&#c, liber_rs::from::CS::CSTaskGroup)),
+            init: |c| quote!(liber_rs::from::CS::register_task_shim::<#c>),
+        },
+        Slot {
+            name: "free_task",
+            signature: |c| quote!(extern "C" fn(&#c)),
+            init: |c| quote!(liber_rs::from::CS::free_task_shim::<#c>),
+        },
+    ]);
+    slots
+}
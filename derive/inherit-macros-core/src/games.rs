@@ -0,0 +1,51 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+#[cfg(not(any(
+    feature = "eldenring",
+    feature = "sekiro",
+    feature = "ds3",
+    feature = "ac6"
+)))]
+compile_error!("select the game with one of the `eldenring`, `sekiro`, `ds3` or `ac6` features");
+#[cfg(any(
+    all(
+        feature = "eldenring",
+        any(feature = "sekiro", feature = "ds3", feature = "ac6")
+    ),
+    all(feature = "sekiro", any(feature = "ds3", feature = "ac6")),
+    all(feature = "ds3", feature = "ac6"),
+))]
+compile_error!("only one of the `eldenring`, `sekiro`, `ds3` and `ac6` features can be enabled");
+
+/// The game the vtables are generated for, the same as `liber_rs::Game`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Game {
+    EldenRing,
+    Sekiro,
+    DarkSouls3,
+    ArmoredCore6,
+}
+
+impl Game {
+    /// The game selected with the features of the crate, which liber-rs forwards.
+    pub(crate) const SELECTED: Game = if cfg!(feature = "sekiro") {
+        Game::Sekiro
+    } else if cfg!(feature = "ds3") {
+        Game::DarkSouls3
+    } else if cfg!(feature = "ac6") {
+        Game::ArmoredCore6
+    } else {
+        Game::EldenRing
+    };
+
+    /// The path of the game in liber-rs.
+    pub(crate) fn path(self) -> TokenStream {
+        match self {
+            Game::EldenRing => quote!(liber_rs::Game::EldenRing),
+            Game::Sekiro => quote!(liber_rs::Game::Sekiro),
+            Game::DarkSouls3 => quote!(liber_rs::Game::DarkSouls3),
+            Game::ArmoredCore6 => quote!(liber_rs::Game::ArmoredCore6),
+        }
+    }
+}
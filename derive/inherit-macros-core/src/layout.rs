@@ -0,0 +1,200 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use syn::spanned::Spanned;
+use syn::{
+    parse2, parse_quote, Attribute, Data, DeriveInput, Error, Expr, Field, Fields, ItemStruct,
+    LitInt, Visibility,
+};
+
+/// Layout of a struct as declared with the `offset`, `size` and `vtable` attributes.
+struct Layout {
+    /// Offsets are relative to `CppClass<Self>`, which starts with the vtable pointer, instead of `Self`.
+    vtable: bool,
+    size: Option<LitInt>,
+}
+
+impl Layout {
+    fn from_attrs(attrs: &[Attribute]) -> Result<Self, Error> {
+        let mut layout = Layout {
+            vtable: false,
+            size: None,
+        };
+        for attr in attrs {
+            if attr.path.is_ident("vtable") {
+                layout.vtable = true;
+            } else if attr.path.is_ident("size") {
+                if layout.size.is_some() {
+                    return Err(Error::new(attr.span(), "`size` specified twice"));
+                }
+                layout.size = Some(attr.parse_args()?);
+            }
+        }
+        Ok(layout)
+    }
+
+    /// Offset of the first field of the struct.
+    fn start(&self) -> TokenStream {
+        if self.vtable {
+            quote!(::core::mem::size_of::<usize>())
+        } else {
+            quote!(0usize)
+        }
+    }
+}
+
+/// The offset of `field`, which every field has to declare, so no offset goes unchecked. It is usually a
+/// literal, the padding inserted by `cpp_layout` is at the end of the previous field.
+fn field_offset(field: &Field) -> Result<Expr, Error> {
+    let mut offsets = field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("offset"));
+    let offset = match offsets.next() {
+        Some(attr) => attr.parse_args()?,
+        None => {
+            let name = field.ident.as_ref().unwrap();
+            return Err(Error::new(
+                field.span(),
+                format!("`{name}` has no `#[offset]`, every field needs one"),
+            ));
+        }
+    };
+    if let Some(attr) = offsets.next() {
+        return Err(Error::new(attr.span(), "`offset` specified twice"));
+    }
+    Ok(offset)
+}
+
+fn check_repr_c(attrs: &[Attribute], span: Span) -> Result<(), Error> {
+    let repr_c = attrs
+        .iter()
+        .any(|attr| attr.path.is_ident("repr") && attr.tokens.to_string().to_uppercase() == "(C)");
+    if repr_c {
+        Ok(())
+    } else {
+        Err(Error::new(span, "Type must have `#[repr(C)]` attribute"))
+    }
+}
+
+pub fn cpp_layout_impl(input: TokenStream) -> TokenStream {
+    cpp_layout_internal(input).unwrap_or_else(|e| e.to_compile_error())
+}
+
+fn cpp_layout_internal(input: TokenStream) -> Result<TokenStream, Error> {
+    let input = parse2::<DeriveInput>(input)?;
+    check_repr_c(&input.attrs, input.span())?;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "`CppLayout` does not support generic types",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            fields => {
+                return Err(Error::new(
+                    fields.span(),
+                    "`CppLayout` requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                input.span(),
+                "`CppLayout` can only be derived for structs",
+            ))
+        }
+    };
+
+    let layout = Layout::from_attrs(&input.attrs)?;
+    let ident = &input.ident;
+    let (offset_base, size_of) = if layout.vtable {
+        (
+            quote!(liber_rs::CppClass::<#ident>::DATA_OFFSET +),
+            quote!(::core::mem::size_of::<liber_rs::CppClass<#ident>>()),
+        )
+    } else {
+        (TokenStream::new(), quote!(::core::mem::size_of::<#ident>()))
+    };
+
+    let mut asserts = vec![];
+    for field in fields {
+        let offset = field_offset(field)?;
+        let name = field.ident.as_ref().unwrap();
+        let message = format!(
+            "`{ident}::{name}` is not at offset {}",
+            offset.to_token_stream()
+        );
+        asserts.push(quote! {
+            assert!(#offset_base ::core::mem::offset_of!(#ident, #name) == #offset, #message);
+        });
+    }
+    if let Some(size) = layout.size {
+        let message = format!("`{ident}` is not of size {size}");
+        asserts.push(quote!(assert!(#size_of == #size, #message);));
+    }
+
+    Ok(quote! {
+        const _: () = {
+            #(#asserts)*
+        };
+    })
+}
+
+fn padding(name: Ident, offset: &TokenStream, len: TokenStream) -> Field {
+    Field {
+        attrs: vec![parse_quote!(#[offset(#offset)])],
+        vis: Visibility::Inherited,
+        ident: Some(name),
+        colon_token: Some(Default::default()),
+        ty: parse_quote!([u8; #len]),
+    }
+}
+
+pub fn cpp_layout_attr_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
+    cpp_layout_attr_internal(attr, item).unwrap_or_else(|e| e.to_compile_error())
+}
+
+fn cpp_layout_attr_internal(attr: TokenStream, item: TokenStream) -> Result<TokenStream, Error> {
+    if !attr.is_empty() {
+        return Err(Error::new(attr.span(), "`cpp_layout` takes no arguments"));
+    }
+    let mut input = parse2::<ItemStruct>(item)?;
+    check_repr_c(&input.attrs, input.span())?;
+    let layout = Layout::from_attrs(&input.attrs)?;
+    let fields = match &mut input.fields {
+        Fields::Named(fields) => &mut fields.named,
+        fields => {
+            return Err(Error::new(
+                fields.span(),
+                "`cpp_layout` requires a struct with named fields",
+            ))
+        }
+    };
+
+    // The end of the previous field, as a constant expression.
+    let mut end = layout.start();
+    let mut padded = syn::punctuated::Punctuated::new();
+    for field in std::mem::take(fields) {
+        let ty = &field.ty;
+        let offset = field_offset(&field)?;
+        let name = format_ident!("_pad_{}", field.ident.as_ref().unwrap());
+        padded.push(padding(name, &end, quote!(#offset - (#end))));
+        end = quote!((#offset) + ::core::mem::size_of::<#ty>());
+        padded.push(field);
+    }
+    if let Some(size) = &layout.size {
+        padded.push(padding(
+            format_ident!("_pad_tail"),
+            &end,
+            quote!(#size - (#end)),
+        ));
+    }
+    *fields = padded;
+
+    input
+        .attrs
+        .insert(0, parse_quote!(#[derive(liber_rs::CppLayout)]));
+    Ok(input.to_token_stream())
+}
@@ -1,7 +1,15 @@
 #![doc = include_str!("../README.md")]
 
+mod bases;
+mod games;
+mod layout;
 mod tests;
+mod util;
 
+pub use crate::layout::{cpp_layout_attr_impl, cpp_layout_impl};
+
+use crate::bases::{base_class, BaseClass, BASE_CLASS_NAMES};
+use crate::util::check_field_name;
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::{format_ident, quote, ToTokens, TokenStreamExt};
 use std::collections::HashMap;
@@ -31,16 +39,17 @@ fn inherit_cs_ez_task_attr_internal(
     attr: TokenStream,
     item: TokenStream,
 ) -> Result<TokenStream, Error> {
+    let base = base_class("CSEzTask").unwrap();
     let mut input = parse2::<ItemStruct>(item)?;
     let checks = enforce_repr_c(&mut input)?;
-    let fields = enforce_first_field(&mut input)?;
+    let fields = enforce_first_field(&mut input, &base)?;
     let params = get_params(fields);
 
     let ident = input.ident;
     input.ident = format_ident!("{}Type", ident);
 
     let args = parse_args(attr, ident)?;
-    let inherited = inherit_cs_easy_task(input.ident.to_string(), params, args);
+    let inherited = inherit_class(&base, input.ident.to_string(), params, args)?;
     let mut tokenstream = input.to_token_stream();
     tokenstream.append_all([inherited, checks]);
     Ok(tokenstream)
@@ -48,7 +57,7 @@ fn inherit_cs_ez_task_attr_internal(
 
 fn parse_args(attr: TokenStream, ident: Ident) -> Result<AttrArgs, Error> {
     let attr = attr.to_string();
-    let args = attr.split(',');
+    let args = attr.split(',').filter(|arg| !arg.trim().is_empty());
     let mut dict = HashMap::new();
     for arg in args {
         let mut split = arg.split('=');
@@ -72,7 +81,6 @@ fn parse_args(attr: TokenStream, ident: Ident) -> Result<AttrArgs, Error> {
     let args = AttrArgs {
         destructor: dict.remove("destructor"),
     };
-    eprintln!("{args:?}");
     Ok(args)
 }
 
@@ -120,18 +128,16 @@ fn enforce_repr_c_attr(input: &mut ItemStruct) -> Result<(), Error> {
     Ok(())
 }
 
-fn enforce_first_field(input: &mut ItemStruct) -> Result<&Fields, Error> {
-    fn check_first_field(first: &Field) -> bool {
-        first
-            .ty
-            .to_token_stream()
-            .to_string()
-            .ends_with("CSEzTaskType")
-    }
+fn enforce_first_field<'a>(
+    input: &'a mut ItemStruct,
+    base: &BaseClass,
+) -> Result<&'a Fields, Error> {
+    let base_type_name = format!("{}Type", base.name);
+    let type_path = &base.type_path;
 
     match &mut input.fields {
         Fields::Named(n) => {
-            if !check_first_field(n.named.first().unwrap()) {
+            if !check_field_name(n.named.first().unwrap(), &base_type_name) {
                 n.named.insert(
                     0,
                     Field {
@@ -139,13 +145,13 @@ fn enforce_first_field(input: &mut ItemStruct) -> Result<&Fields, Error> {
                         vis: Visibility::Inherited,
                         ident: Some(format_ident!("task")),
                         colon_token: None,
-                        ty: Type::Verbatim(quote!(liber_rs::from::CS::CSEzTaskType)),
+                        ty: Type::Verbatim(quote!(#type_path)),
                     },
                 )
             }
         }
         Fields::Unnamed(u) => {
-            if !check_first_field(u.unnamed.first().unwrap()) {
+            if !check_field_name(u.unnamed.first().unwrap(), &base_type_name) {
                 u.unnamed.insert(
                     0,
                     Field {
@@ -153,7 +159,7 @@ fn enforce_first_field(input: &mut ItemStruct) -> Result<&Fields, Error> {
                         vis: Visibility::Inherited,
                         ident: None,
                         colon_token: None,
-                        ty: Type::Verbatim(quote!(liber_rs::from::CS::CSEzTaskType)),
+                        ty: Type::Verbatim(quote!(#type_path)),
                     },
                 )
             }
@@ -168,7 +174,7 @@ fn enforce_first_field(input: &mut ItemStruct) -> Result<&Fields, Error> {
                 vis: Visibility::Inherited,
                 ident: None,
                 colon_token: None,
-                ty: Type::Verbatim(quote!(liber_rs::from::CS::CSEzTaskType)),
+                ty: Type::Verbatim(quote!(#type_path)),
             });
             input.fields = Fields::Unnamed(fields);
         }
@@ -181,7 +187,7 @@ fn enforce_first_field(input: &mut ItemStruct) -> Result<&Fields, Error> {
 struct Params {
     names: TokenStream,
     field_types: TokenStream,
-    task_field_name: Option<Ident>,
+    base_field_name: Option<Ident>,
 }
 
 pub fn inherit_cs_ez_task_impl(input: TokenStream) -> TokenStream {
@@ -190,62 +196,139 @@ pub fn inherit_cs_ez_task_impl(input: TokenStream) -> TokenStream {
 
 fn inherit_cs_ez_task_internal(input: TokenStream) -> Result<TokenStream, Error> {
     let input = parse2::<DeriveInput>(input)?;
+    let base = base_class("CSEzTask").unwrap();
 
     let ident = get_structure_name(&input)?;
 
     let checks = is_repr_c(&input)?;
-    let fields = check_and_get_fields(&input)?;
+    let fields = check_and_get_fields(&input, &base)?;
 
     let params = get_params(&fields);
 
-    let mut tokenstream = inherit_cs_easy_task(ident, params, AttrArgs { destructor: None });
+    let mut tokenstream = inherit_class(&base, ident, params, AttrArgs { destructor: None })?;
     tokenstream.append_all(checks);
     Ok(tokenstream)
 }
 
-fn inherit_cs_easy_task(ident: String, fields: Params, args: AttrArgs) -> TokenStream {
+pub fn inherit_impl(input: TokenStream) -> TokenStream {
+    inherit_internal(input).unwrap_or_else(|e| e.to_compile_error())
+}
+
+fn inherit_internal(input: TokenStream) -> Result<TokenStream, Error> {
+    let input = parse2::<DeriveInput>(input)?;
+
+    let ident = get_structure_name(&input)?;
+    let (base, args) = get_inherit_args(&input)?;
+
+    let checks = is_repr_c(&input)?;
+    let fields = check_and_get_fields(&input, &base)?;
+
+    let params = get_params(&fields);
+
+    let mut tokenstream = inherit_class(&base, ident, params, args)?;
+    tokenstream.append_all(checks);
+    Ok(tokenstream)
+}
+
+/// Reads the `#[inherit(Base, destructor = path)]` helper attribute of the `Inherit` derive.
+fn get_inherit_args(input: &DeriveInput) -> Result<(BaseClass, AttrArgs), Error> {
+    let attr = input
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("inherit"))
+        .ok_or_else(|| {
+            Error::new(
+                input.span(),
+                "Could not find `inherit` attribute. Specify the base class with `#[inherit(BaseClass)]`",
+            )
+        })?;
+
+    let tokens = attr.parse_args::<TokenStream>()?.to_string();
+    let (base_name, args) = tokens.split_once(',').unwrap_or((&tokens, ""));
+    let base_name = base_name.replace(' ', "");
+    let base = base_class(&base_name).ok_or_else(|| {
+        Error::new(
+            attr.span(),
+            format!("Unknown base class `{base_name}`. Supported base classes: {BASE_CLASS_NAMES}"),
+        )
+    })?;
+
+    let ident = input.ident.to_string();
+    let class_name = format_ident!("{}", &ident[..ident.len() - 4]);
+    let args = parse_args(TokenStream::from_str(args)?, class_name)?;
+
+    Ok((base, args))
+}
+
+fn inherit_class(
+    base: &BaseClass,
+    ident: String,
+    fields: Params,
+    args: AttrArgs,
+) -> Result<TokenStream, Error> {
     let Params {
         names,
         field_types,
-        task_field_name,
+        base_field_name,
     } = fields;
+    let BaseClass {
+        class_path,
+        type_path,
+        ..
+    } = base;
 
     let class_name = &ident[..ident.len() - 4];
     let class_name_type_ident = format_ident!("{class_name}Type");
     let class_name_ident = format_ident!("{class_name}");
     let vtable_name = format_ident!("{class_name}VTable");
-    let struct_type_specific = match task_field_name {
+
+    // Bases that can not be default constructed, like `DLRuntimeClass`, are passed to `new` instead.
+    let base_param = base_field_name
+        .clone()
+        .unwrap_or_else(|| format_ident!("base"));
+    let (new_params, base_init) = if base.component {
+        (
+            field_types,
+            quote!(let #base_param = <#type_path as Default>::default();),
+        )
+    } else {
+        (
+            quote!(#base_param: #type_path, #field_types),
+            TokenStream::new(),
+        )
+    };
+    let struct_type_specific = match base_field_name {
         None => quote! {
             impl #class_name_ident {
-                pub fn new(#field_types) -> Self {
-                    let task = liber_rs::from::CS::CSEzTaskType::new();
+                pub fn new(#new_params) -> Self {
+                    #base_init
                     Self(liber_rs::CppClass::<
                         #class_name_type_ident,
-                    >::from_data(#class_name_type_ident(task, #names)))
+                    >::from_data(#class_name_type_ident(#base_param, #names)))
                 }
             }
             impl std::ops::Deref for #class_name_type_ident {
-                type Target = liber_rs::from::CS::CSEzTaskType;
+                type Target = #type_path;
 
                 fn deref(&self) -> &Self::Target {
                     &self.0
                 }
             }
         },
-        Some(task_field_name) => quote! {
+        Some(base_field_name) => quote! {
             impl #class_name_ident {
-                pub fn new(#field_types) -> Self {
-                    let #task_field_name = liber_rs::from::CS::CSEzTaskType::new();
+                pub fn new(#new_params) -> Self {
+                    #base_init
                     Self(liber_rs::CppClass::<
                         #class_name_type_ident,
-                    >::from_data(#class_name_type_ident { #task_field_name, #names }))
+                    >::from_data(#class_name_type_ident { #base_field_name, #names }))
                 }
             }
             impl std::ops::Deref for #class_name_type_ident {
-                type Target = liber_rs::from::CS::CSEzTaskType;
+                type Target = #type_path;
 
                 fn deref(&self) -> &Self::Target {
-                    &self.#task_field_name
+                    &self.#base_field_name
                 }
             }
         },
@@ -262,92 +345,156 @@ fn inherit_cs_easy_task(ident: String, fields: Params, args: AttrArgs) -> TokenS
                 &self.0
             }
         }
+
+        impl<'a> From<&'a liber_rs::CppClass<#class_name_type_ident>> for &'a #class_name_ident {
+            fn from(class: &'a liber_rs::CppClass<#class_name_type_ident>) -> Self {
+                unsafe { &*(class as *const liber_rs::CppClass<#class_name_type_ident> as *const #class_name_ident) }
+            }
+        }
     };
+
+    let slot_names: Vec<Ident> = base
+        .slots
+        .iter()
+        .map(|slot| format_ident!("{}", slot.name))
+        .collect();
+    let slot_types = base
+        .slots
+        .iter()
+        .map(|slot| (slot.signature)(&class_name_ident));
+    let slot_inits = base.slots.iter().map(|slot| (slot.init)(&class_name_ident));
     let vtable = quote! {
         #[repr(C)]
         pub struct #vtable_name {
-            get_runtime_class: extern "C" fn(&#class_name_ident) -> &'static liber_rs::from::DLRF::DLRuntimeClass,
-            destructor: extern "C" fn(&#class_name_ident),
-            execute: extern "C" fn(&#class_name_ident, &liber_rs::from::FD4::FD4TaskData),
-            eztask_execute: extern "C" fn(&#class_name_ident, &liber_rs::from::FD4::FD4TaskData),
-            register_task: extern "C" fn(&#class_name_ident, task_group: liber_rs::from::CS::CSTaskGroup),
-            free_task: extern "C" fn(&#class_name_ident),
+            #(#slot_names: #slot_types,)*
         }
     };
 
-    let fd4_component_impl = match args.destructor {
-        Some(d) => {
-            let str = LitStr::new(&d, Span::call_site());
-            let destructor = str.parse_with(Path::parse_mod_style).unwrap();
-            quote! {
-                impl FD4ComponentBaseTrait for #class_name_ident {
-                    extern "C" fn destructor(&self) {
-                        #destructor(self);
-                        unsafe { liber_rs::from::CS::CSEzTask::destructor(&*(self as *const #class_name_ident as *const liber_rs::from::CS::CSEzTask)) };
-                    }
-                }
-            }
-        }
-        None => quote! {
-            impl liber_rs::from::FD4::FD4ComponentBaseTrait for #class_name_ident {}
-        },
-    };
-
     let impls = quote! {
         impl #vtable_name {
             pub const fn new() -> Self {
                 Self {
-                    get_runtime_class: <#class_name_ident as liber_rs::from::FD4::DLRuntimeClassTrait>::get_runtime_class,
-                    destructor: <#class_name_ident as liber_rs::from::FD4::FD4ComponentBaseTrait>::destructor,
-                    execute: <#class_name_ident as liber_rs::from::FD4::FD4TaskBaseTrait>::execute,
-                    eztask_execute: <#class_name_ident as liber_rs::from::CS::CSEzTaskTrait>::eztask_execute,
-                    register_task: <#class_name_ident as liber_rs::from::CS::CSEzTaskTrait>::register_task,
-                    free_task: <#class_name_ident as liber_rs::from::CS::CSEzTaskTrait>::free_task,
+                    #(#slot_names: #slot_inits,)*
                 }
             }
         }
-        impl liber_rs::VTable for #class_name_type_ident {
+        impl liber_rs::VTableLayout for #class_name_type_ident {
             type Table = #vtable_name;
+        }
+        impl liber_rs::VTable for #class_name_type_ident {
             const TABLE: &'static Self::Table = &#vtable_name::new();
         }
-        impl liber_rs::from::FD4::FD4TaskBaseTrait for #class_name_ident {
-            extern "C" fn execute(&self, data: &FD4TaskData) {
-                self.eztask_execute(data)
+    };
+
+    let bases = std::iter::once(type_path).chain(&base.ancestors);
+    let inherits = quote! {
+        #(unsafe impl liber_rs::Inherits<#bases> for #class_name_type_ident {})*
+    };
+
+    // The generated vtable is laid out exactly like the vtables of the base classes, which lets the virtual call
+    // traits, like `CSEzTaskVirtual`, dispatch through it.
+    let vtables = &base.vtables;
+    let own_vtable = vtables.last().unwrap();
+    let game = base.game.path();
+    let layouts = quote! {
+        #(
+            impl AsRef<#vtables<#class_name_type_ident>> for #vtable_name {
+                fn as_ref(&self) -> &#vtables<#class_name_type_ident> {
+                    unsafe { &*(self as *const Self as *const #vtables<#class_name_type_ident>) }
+                }
             }
-        }
-        #fd4_component_impl
+        )*
+        const _: () = assert!(
+            std::mem::size_of::<#vtable_name>()
+                == std::mem::size_of::<#own_vtable<#class_name_type_ident>>()
+        );
+        const _: () = assert!(
+            matches!(liber_rs::GAME, #game),
+            "the vtable is generated for another game than the one liber-rs is built for"
+        );
     };
-    let reflection = quote! {
-        impl liber_rs::from::FD4::DLRuntimeClassTrait for #class_name_ident {
-            extern "C" fn get_runtime_class(&self) -> &'static liber_rs::from::DLRF::DLRuntimeClass {
-                static DL_RUNTIME_CLASS: liber_rs::from::DLRF::DLRuntimeClass =
-                    liber_rs::from::DLRF::DLRuntimeClass::from_data(liber_rs::from::DLRF::DLRuntimeClassType::new(
-                        liber_rs::cstr!(#class_name),
-                        liber_rs::widecstr!(#class_name),
-                    ));
-                &DL_RUNTIME_CLASS
+
+    let fd4_component_impl = match (args.destructor, base.component) {
+        (Some(d), true) => {
+            let str = LitStr::new(&d, Span::call_site());
+            let destructor = str.parse_with(Path::parse_mod_style)?;
+            quote! {
+                impl liber_rs::from::FD4::FD4ComponentBaseTrait for #class_name_ident {
+                    fn destructor(&self) {
+                        #destructor(self);
+                        <#class_path as liber_rs::from::FD4::FD4ComponentBaseTrait>::destructor(
+                            self.0.upcast::<#type_path>(),
+                        );
+                    }
+                }
             }
         }
+        (None, true) => quote! {
+            impl liber_rs::from::FD4::FD4ComponentBaseTrait for #class_name_ident {}
+        },
+        (Some(_), false) => {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("`{}` does not have a destructor to override.", base.name),
+            ));
+        }
+        (None, false) => TokenStream::new(),
     };
-    let checks = quote! {
-        const _:() = {
-            const fn is_cs_ez_task(t: &dyn liber_rs::from::CS::CSEzTaskTrait) {}
-            const fn new_test_type() -> #class_name_ident {
-                unsafe { core::mem::MaybeUninit::uninit().assume_init() }
+    let final_impls = (base.final_impls)(&class_name_ident);
+
+    let reflection = if base.component {
+        quote! {
+            impl liber_rs::from::FD4::DLRuntimeClassTrait for #class_name_ident {
+                fn runtime_class() -> &'static liber_rs::from::DLRF::DLRuntimeClass {
+                    static DL_RUNTIME_CLASS: liber_rs::from::DLRF::DLRuntimeClass =
+                        liber_rs::from::DLRF::DLRuntimeClass::from_data(liber_rs::from::DLRF::DLRuntimeClassType::new::<#class_name_ident>(
+                            liber_rs::cstr!(#class_name),
+                            liber_rs::widecstr!(#class_name),
+                        ));
+                    &DL_RUNTIME_CLASS
+                }
+            }
+            impl liber_rs::RuntimeType for #class_name_type_ident {
+                type Root = liber_rs::from::FD4::FD4ComponentBaseType;
+                fn is_instance(root: &liber_rs::from::FD4::FD4ComponentBase) -> bool {
+                    liber_rs::from::FD4::has_runtime_class(
+                        root,
+                        <#class_name_ident as liber_rs::from::FD4::DLRuntimeClassTrait>::runtime_class(),
+                    )
+                }
             }
-            let t = new_test_type();
-            is_cs_ez_task(&t);
-            std::mem::forget(t);
-        };
+        }
+    } else {
+        TokenStream::new()
+    };
+
+    let checks = match &base.user_trait {
+        Some(user_trait) => quote! {
+            const _: () = {
+                fn implements<T: ?Sized + #user_trait>() {}
+                let _ = implements::<#class_name_ident>;
+            };
+        },
+        None => TokenStream::new(),
     };
 
-    tokenstream.append_all(&[struct_type_specific, vtable, impls, reflection, checks]);
-    tokenstream
+    tokenstream.append_all(&[
+        struct_type_specific,
+        vtable,
+        impls,
+        layouts,
+        inherits,
+        fd4_component_impl,
+        final_impls,
+        reflection,
+        checks,
+    ]);
+    Ok(tokenstream)
 }
 
 fn get_structure_name(input: &DeriveInput) -> Result<String, Error> {
     let ident = input.ident.to_string();
-    if &ident[ident.len() - 4..] != "Type" {
+    if !ident.ends_with("Type") || ident.len() == 4 {
         return Err(Error::new(
             input.ident.span(),
             "Derive must be used on a type that ends with the word 'Type'",
@@ -390,26 +537,22 @@ fn has_repr_c_attr(input: &DeriveInput) -> Result<(), Error> {
     ))
 }
 
-fn check_and_get_fields(input: &DeriveInput) -> Result<Fields, Error> {
+fn check_and_get_fields(input: &DeriveInput, base: &BaseClass) -> Result<Fields, Error> {
     let fields = match &input.data {
         Data::Struct(s) => {
             let first = match &s.fields {
-                Fields::Named(n) => n.named.first().unwrap(),
-                Fields::Unnamed(u) => u.unnamed.first().unwrap(),
+                Fields::Named(n) => n.named.first(),
+                Fields::Unnamed(u) => u.unnamed.first(),
                 Fields::Unit => {
                     return Err(Error::new(input.span(), "Unit types are not supported."));
                 }
             };
 
-            if !first
-                .ty
-                .to_token_stream()
-                .to_string()
-                .ends_with("CSEzTaskType")
-            {
+            let base_type_name = format!("{}Type", base.name);
+            if !first.is_some_and(|first| check_field_name(first, &base_type_name)) {
                 return Err(Error::new(
-                    first.ty.span(),
-                    "First field of a class that inherits `CSEzTask` MUST be of type `CSEzTaskType`. Additional fields can go AFTER this field.",
+                    first.map_or(input.span(), |first| first.ty.span()),
+                    format!("First field of a class that inherits `{}` MUST be of type `{base_type_name}`. Additional fields can go AFTER this field.", base.name),
                 ));
             }
 
@@ -418,7 +561,10 @@ fn check_and_get_fields(input: &DeriveInput) -> Result<Fields, Error> {
         _ => {
             return Err(Error::new(
                 input.span(),
-                "Only structures are supported for inheriting `CSEzTask`.",
+                format!(
+                    "Only structures are supported for inheriting `{}`.",
+                    base.name
+                ),
             ));
         }
     };
@@ -430,19 +576,19 @@ fn get_params(fields: &Fields) -> Params {
     match fields {
         Fields::Named(n) => {
             let mut fields_iter = n.named.iter();
-            let task_field_name = fields_iter.next().unwrap().clone().ident.unwrap();
+            let base_field_name = fields_iter.next().unwrap().clone().ident.unwrap();
             let mut fields: Punctuated<Field, Comma> = Punctuated::new();
             let mut names = vec![];
 
             for field in fields_iter {
-                fields.push(field.clone());
+                fields.push(as_param(field));
                 names.push(field.ident.as_ref().unwrap().to_string());
             }
 
             Params {
                 field_types: TokenStream::from_str(&fields.to_token_stream().to_string()).unwrap(),
                 names: TokenStream::from_str(&names.join(", ")).unwrap(),
-                task_field_name: Some(task_field_name),
+                base_field_name: Some(base_field_name),
             }
         }
         Fields::Unnamed(u) => {
@@ -451,11 +597,11 @@ fn get_params(fields: &Fields) -> Params {
             let mut fields: Punctuated<Field, Comma> = Punctuated::new();
             let mut names = vec![];
 
-            let count = 1;
-            for field in fields_iter {
-                let field_name = format!("f{count}");
-                let mut new_field = field.clone();
+            for (count, field) in fields_iter.enumerate() {
+                let field_name = format!("f{}", count + 1);
+                let mut new_field = as_param(field);
                 new_field.ident = Some(format_ident!("{field_name}"));
+                new_field.colon_token = Some(Default::default());
                 fields.push(new_field);
                 names.push(field_name);
             }
@@ -463,9 +609,17 @@ fn get_params(fields: &Fields) -> Params {
             Params {
                 field_types: TokenStream::from_str(&fields.to_token_stream().to_string()).unwrap(),
                 names: TokenStream::from_str(&names.join(", ")).unwrap(),
-                task_field_name: None,
+                base_field_name: None,
             }
         }
         Fields::Unit => unreachable!(),
     }
 }
+
+/// Strip everything from a field that is not allowed in a function parameter.
+fn as_param(field: &Field) -> Field {
+    let mut param = field.clone();
+    param.attrs.clear();
+    param.vis = Visibility::Inherited;
+    param
+}
@@ -1,6 +1,8 @@
 #![cfg(test)]
 
-use crate::inherit_cs_ez_task_impl;
+use crate::bases::base_class_for;
+use crate::games::Game;
+use crate::{cpp_layout_attr_impl, cpp_layout_impl, inherit_cs_ez_task_impl, inherit_impl};
 use quote::quote;
 
 #[test]
@@ -8,3 +10,152 @@ fn test() {
     let after = inherit_cs_ez_task_impl(quote!());
     assert_ne!(after.to_string(), "");
 }
+
+#[test]
+fn inherit_uses_base_vtable_layout() {
+    let after = inherit_impl(quote! {
+        #[repr(C)]
+        #[inherit(FD4TaskBase)]
+        pub struct MyTaskType {
+            base: FD4TaskBaseType,
+            value: u32,
+        }
+    })
+    .to_string();
+
+    assert!(!after.contains("compile_error"), "{after}");
+    assert!(after.contains("pub struct MyTaskVTable"));
+    for slot in ["get_runtime_class", "destructor", "execute"] {
+        assert!(
+            after.contains(&format!("{slot} :")),
+            "missing slot `{slot}`"
+        );
+    }
+    assert!(!after.contains("eztask_execute"));
+}
+
+#[test]
+fn inherit_rejects_unknown_base() {
+    let after = inherit_impl(quote! {
+        #[repr(C)]
+        #[inherit(NotAClass)]
+        pub struct MyTaskType {
+            base: FD4TaskBaseType,
+        }
+    })
+    .to_string();
+
+    assert!(after.contains("compile_error"));
+    assert!(after.contains("Unknown base class"));
+}
+
+#[test]
+fn cpp_layout_asserts_offsets_and_size() {
+    let after = cpp_layout_impl(quote! {
+        #[repr(C)]
+        #[vtable]
+        #[size(0x20)]
+        pub struct MyProxyType {
+            #[offset(0x8)]
+            base: FD4TaskBaseType,
+            #[offset(0x10)]
+            value: u32,
+        }
+    })
+    .to_string();
+
+    assert!(!after.contains("compile_error"), "{after}");
+    assert!(after.contains("DATA_OFFSET"));
+    assert!(after.contains("`MyProxyType::base` is not at offset 0x8"));
+    assert!(after.contains("`MyProxyType::value` is not at offset 0x10"));
+    assert!(after.contains("`MyProxyType` is not of size 0x20"));
+}
+
+#[test]
+fn cpp_layout_requires_every_offset() {
+    let after = cpp_layout_impl(quote! {
+        #[repr(C)]
+        pub struct MyProxyType {
+            #[offset(0x0)]
+            base: FD4TaskBaseType,
+            value: u32,
+        }
+    })
+    .to_string();
+
+    assert!(after.contains("compile_error"));
+    assert!(after.contains("`value` has no `#[offset]`"));
+}
+
+#[test]
+fn cpp_layout_attr_inserts_padding() {
+    let after = cpp_layout_attr_impl(
+        quote!(),
+        quote! {
+            #[repr(C)]
+            #[size(0x40)]
+            pub struct Partial {
+                #[offset(0x0)]
+                a: u32,
+                #[offset(0x30)]
+                b: u16,
+            }
+        },
+    )
+    .to_string();
+
+    assert!(!after.contains("compile_error"), "{after}");
+    assert!(after.contains("_pad_a"));
+    assert!(after.contains("_pad_b"));
+    assert!(after.contains("_pad_tail"));
+    assert!(after.contains("derive (liber_rs :: CppLayout)"));
+
+    // The padding declares its offsets as well, so the derive checks every field.
+    let derived = cpp_layout_impl(after.parse().unwrap()).to_string();
+    assert!(!derived.contains("compile_error"), "{derived}");
+    assert!(derived.contains("`Partial::_pad_b` is not at offset"));
+    assert!(derived.contains("`Partial::b` is not at offset 0x30"));
+}
+
+#[test]
+fn inherit_checks_the_game_of_liber_rs() {
+    let after = inherit_impl(quote! {
+        #[repr(C)]
+        #[inherit(CSEzTask)]
+        pub struct MyTaskType {
+            base: CSEzTaskType,
+        }
+    })
+    .to_string();
+
+    let game = Game::SELECTED.path().to_string();
+    assert!(
+        after.contains(&format!("matches ! (liber_rs :: GAME , {game})")),
+        "{after}"
+    );
+}
+
+#[test]
+fn every_game_has_the_base_classes() {
+    for game in [
+        Game::EldenRing,
+        Game::Sekiro,
+        Game::DarkSouls3,
+        Game::ArmoredCore6,
+    ] {
+        let base = base_class_for(game, "CSEzTask").unwrap();
+        assert_eq!(base.game, game);
+        let slots = base.slots.iter().map(|slot| slot.name).collect::<Vec<_>>();
+        assert_eq!(
+            slots,
+            [
+                "get_runtime_class",
+                "destructor",
+                "execute",
+                "eztask_execute",
+                "register_task",
+                "free_task"
+            ]
+        );
+    }
+}
@@ -1,6 +1,9 @@
 #![doc = include_str!("../README.md")]
 
-use inherit_macros_core::{inherit_cs_ez_task_attr_impl, inherit_cs_ez_task_impl};
+use inherit_macros_core::{
+    cpp_layout_attr_impl, cpp_layout_impl, inherit_cs_ez_task_attr_impl, inherit_cs_ez_task_impl,
+    inherit_impl,
+};
 use proc_macro::TokenStream;
 use proc_macro_error::proc_macro_error;
 
@@ -36,3 +39,60 @@ pub fn inherit_cs_ez_task(input: TokenStream) -> TokenStream {
 pub fn cs_ez_task(attr: TokenStream, item: TokenStream) -> TokenStream {
     inherit_cs_ez_task_attr_impl(attr.into(), item.into()).into()
 }
+
+/// Automatically implements most of what the user needs to "inherit" one of the libER base classes. The base
+/// class is named with the `#[inherit(BaseClass)]` attribute, and can be any of `DLRuntimeClass`,
+/// `FD4ComponentBase`, `FD4TaskBase` or `CSEzTask`. The same rules as the `CSEzTask` derive apply: The structure
+/// must be `#[repr(C)]`, end with the name `Type`, and the first field in the structure must be of the data type
+/// of the base class, i.e. `FD4TaskBaseType` for `#[inherit(FD4TaskBase)]`.
+///
+/// A destructor can be provided for bases that derive from `FD4ComponentBase` with
+/// `#[inherit(FD4TaskBase, destructor = path::to::destructor)]`. The base class destructor is called after it.
+///
+/// # Generated
+/// A list of code generated for your inheritance.
+///
+/// > Newtype class that wraps `CppClass<C>` with `C` being your custom type.
+/// > Structure for the `VTable` of your custom type, laid out like the vtable of the base class, and the
+/// associated implementation of the `VTable` for your type.
+/// > `Deref` implementations that deref the newtype into the underlying `CppClass<C>` and your custom type into
+/// the data type of the base class.
+/// > A function called `new` for your newtype which takes in all fields in the user defined structure after the
+/// base class. Bases that can not be default constructed, like `DLRuntimeClassType`, are passed in first.
+/// > Implementations for the traits of the base classes that the user does not have to provide, like
+/// `DLRuntimeClassTrait` with a `DLRuntimeClass` for your class, and `FD4ComponentBaseTrait`.
+/// > A compile time check to enforce that your class implements the trait of the base class, i.e.
+/// `FD4TaskBaseTrait` for `FD4TaskBase`.
+#[proc_macro_error]
+#[proc_macro_derive(Inherit, attributes(inherit))]
+pub fn inherit(input: TokenStream) -> TokenStream {
+    inherit_impl(input.into()).into()
+}
+
+/// Checks the layout of a `#[repr(C)]` struct against the offsets and size of the C++ class it mirrors, i.e.
+/// from the libER headers.
+///
+/// Every field is annotated with `#[offset(0x10)]`, and the struct with `#[size(0x18)]`. A compile time
+/// assertion is generated for every one of them, and a field without an offset is an error. If the struct is
+/// the data type of a `CppClass`, add `#[vtable]` to the struct, so offsets and size are those of
+/// `CppClass<Self>`, which includes the vtable pointer, like in the headers.
+///
+/// The derive can not change the struct, so every byte has to be declared. Use `#[cpp_layout]` to have the
+/// padding between fields inserted.
+#[proc_macro_error]
+#[proc_macro_derive(CppLayout, attributes(offset, size, vtable))]
+pub fn cpp_layout_derive(input: TokenStream) -> TokenStream {
+    cpp_layout_impl(input.into()).into()
+}
+
+/// Inserts padding fields into a `#[repr(C)]` struct, so every field is at the offset of its `#[offset(0x10)]`
+/// attribute, and the struct has the size of its `#[size(0x18)]` attribute. The layout is then checked with
+/// `#[derive(CppLayout)]`.
+///
+/// This is meant for structures that are only partially known. The padding before a field is named after it,
+/// i.e. `_pad_field`, and the padding at the end is `_pad_tail`.
+#[proc_macro_error]
+#[proc_macro_attribute]
+pub fn cpp_layout(attr: TokenStream, item: TokenStream) -> TokenStream {
+    cpp_layout_attr_impl(attr.into(), item.into()).into()
+}
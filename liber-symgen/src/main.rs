@@ -0,0 +1,228 @@
+//! Generates the symbol database of liber-rs for a game executable on disk, from a file of signatures, so the
+//! RVAs of a new game version can be found without starting the game.
+//!
+//! ```text
+//! liber-symgen <game.exe> <signatures.toml> --version <name> [--output <symbols.toml>]
+//! ```
+//!
+//! The version is added to the database at `--output`, or printed if there is none. Every symbol in the
+//! signature file is found either by a pattern, or by the RTTI of a class for vtables. The first instructions of
+//! symbols in `.text` are added as their prologues, which `SymbolValidator` checks them against:
+//!
+//! ```toml
+//! [symbols."CS::CSEzTask::register_task"]
+//! pattern = "E8 ?? ?? ?? ?? 48 8B 5C 24 30"
+//! # Follow the call the pattern matches. `rip` follows a RIP-relative operand instead.
+//! operand = "branch"
+//!
+//! [symbols."CS::CSEzTaskProxy::vftable"]
+//! rtti = "CS::CSEzTaskProxy"
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use liber_rs::{
+    decode, find_vtable_rva, GameBuild, ImageLayout, Instruction, LiberError, Pattern, PeImage,
+    SymbolDatabase, SymbolSet, MAX_INSTRUCTION_LEN,
+};
+use serde::Deserialize;
+
+mod tests;
+
+/// Prologues are the first instructions of a function that are at least this long.
+const PROLOGUE_LEN: usize = 8;
+
+/// The signatures of the symbols to find.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Signatures {
+    symbols: BTreeMap<String, Signature>,
+}
+
+/// How to find a symbol. Either `pattern` or `rtti` has to be given.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Signature {
+    /// An IDA-style pattern, which has to match once in `section`.
+    pattern: Option<String>,
+    #[serde(default = "text_section")]
+    section: String,
+    /// Added to the address the pattern matches at.
+    #[serde(default)]
+    offset: isize,
+    /// The operand of the instruction at the match to follow, after adding `offset`.
+    operand: Option<Operand>,
+    /// The name of a class, whose vtable is the symbol.
+    rtti: Option<String>,
+}
+
+fn text_section() -> String {
+    ".text".to_string()
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Operand {
+    /// The target of a relative call or jump.
+    Branch,
+    /// The address of a RIP-relative memory operand.
+    Rip,
+}
+
+impl Signature {
+    /// The RVA of the symbol in `image`.
+    fn find(&self, image: &PeImage) -> Result<usize, LiberError> {
+        let (pattern, rtti) = (self.pattern.as_deref(), self.rtti.as_deref());
+        let rva = match (pattern, rtti) {
+            (Some(pattern), None) => {
+                Pattern::new(pattern)?.find_in_section(image, &self.section)?
+            }
+            (None, Some(class)) => return Ok(find_vtable_rva(image, class)? as usize),
+            _ => {
+                return Err(LiberError::InvalidSymbolDatabase(
+                    "a signature needs either a pattern or rtti".into(),
+                ))
+            }
+        };
+        let rva = rva.wrapping_add_signed(self.offset);
+        let Some(operand) = self.operand else {
+            return Ok(rva);
+        };
+        let instruction = instruction_at(image, rva)?;
+        match operand {
+            Operand::Branch => instruction.branch_target,
+            Operand::Rip => instruction.rip_target,
+        }
+        .ok_or(LiberError::InvalidInstruction(rva))
+    }
+}
+
+/// The instruction at `rva` in `image`.
+fn instruction_at(image: &PeImage, rva: usize) -> Result<Instruction, LiberError> {
+    // The instruction may end before the longest instruction would.
+    let code = u32::try_from(rva)
+        .ok()
+        .and_then(|rva| {
+            (1..=MAX_INSTRUCTION_LEN)
+                .rev()
+                .find_map(|len| image.read(rva, len))
+        })
+        .ok_or(LiberError::InvalidInstruction(rva))?;
+    decode(code, rva)
+}
+
+/// The bytes of the first instructions of the function at `rva`, if it is in `.text`.
+fn prologue(image: &PeImage, rva: usize) -> Option<Pattern> {
+    let text = image.section(".text").ok()?;
+    let start = text.virtual_address as usize;
+    if !(start..start + text.virtual_size as usize).contains(&rva) {
+        return None;
+    }
+    let mut len = 0;
+    while len < PROLOGUE_LEN {
+        len += instruction_at(image, rva + len).ok()?.len;
+    }
+    let bytes = image.read(rva as u32, len)?;
+    Pattern::from_mask(bytes, &"x".repeat(len)).ok()
+}
+
+/// The symbols of the executable in `image`, and the errors of the ones that were not found.
+fn generate(
+    image: &PeImage,
+    signatures: &Signatures,
+) -> Result<(SymbolSet, Vec<(String, LiberError)>), LiberError> {
+    let mut symbols = SymbolSet::new(&GameBuild::of(image)?);
+    let mut errors = Vec::new();
+    for (name, signature) in &signatures.symbols {
+        match signature.find(image) {
+            Ok(rva) => {
+                symbols.insert(name.clone(), rva);
+                if let Some(prologue) = prologue(image, rva) {
+                    symbols.insert_prologue(name.clone(), &prologue);
+                }
+            }
+            Err(error) => errors.push((name.clone(), error)),
+        }
+    }
+    Ok((symbols, errors))
+}
+
+struct Args {
+    executable: PathBuf,
+    signatures: PathBuf,
+    version: String,
+    output: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut positional = Vec::new();
+    let mut version = None;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--version" => version = Some(args.next().ok_or("--version needs a name")?),
+            "--output" => output = Some(args.next().ok_or("--output needs a path")?.into()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ => positional.push(PathBuf::from(arg)),
+        }
+    }
+    let [executable, signatures] = <[PathBuf; 2]>::try_from(positional)
+        .map_err(|_| "expected the executable and the signature file".to_string())?;
+    Ok(Args {
+        executable,
+        signatures,
+        version: version.ok_or("--version is required")?,
+        output,
+    })
+}
+
+fn run(args: Args) -> Result<bool, String> {
+    let read = |path: &PathBuf| std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()));
+    let file = read(&args.executable)?;
+    let signatures = String::from_utf8_lossy(&read(&args.signatures)?).into_owned();
+    let signatures: Signatures =
+        toml::from_str(&signatures).map_err(|e| format!("{}: {e}", args.signatures.display()))?;
+    let image = PeImage::new(&file, ImageLayout::File).map_err(|e| e.to_string())?;
+    let (symbols, errors) = generate(&image, &signatures).map_err(|e| e.to_string())?;
+    for (name, error) in &errors {
+        eprintln!("liber-symgen: {name}: {error}");
+    }
+
+    let mut database = match &args.output {
+        Some(output) if output.exists() => {
+            SymbolDatabase::from_file(output).map_err(|e| e.to_string())?
+        }
+        _ => SymbolDatabase::default(),
+    };
+    database.insert_version(args.version, symbols);
+    match &args.output {
+        Some(output) => std::fs::write(output, database.to_toml())
+            .map_err(|e| format!("{}: {e}", output.display()))?,
+        None => print!("{}", database.to_toml()),
+    }
+    Ok(errors.is_empty())
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("liber-symgen: {error}");
+            eprintln!(
+                "usage: liber-symgen <game.exe> <signatures.toml> --version <name> [--output <symbols.toml>]"
+            );
+            return ExitCode::from(2);
+        }
+    };
+    match run(args) {
+        Ok(true) => ExitCode::SUCCESS,
+        // The symbols that were found are written, but the table is incomplete.
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("liber-symgen: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
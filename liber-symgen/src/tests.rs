@@ -0,0 +1,144 @@
+#![cfg(test)]
+
+use liber_rs::{GameBuild, ImageLayout, LiberError, PeImage, SymbolDatabase};
+
+use crate::{generate, parse_args, Signatures};
+
+const TIMESTAMP: u32 = 0x64B0_0000;
+
+/// A file image with a `.text` section at 0x1000 that starts with a call to 0x1100, followed by a load of the
+/// address 0x2000 into `rcx`.
+fn executable() -> Vec<u8> {
+    let mut text = vec![0xCC; 0x200];
+    text[..12].copy_from_slice(&[
+        0xE8, 0xFB, 0x00, 0x00, 0x00, // call 0x1100
+        0x48, 0x8D, 0x0D, 0xF4, 0x0F, 0x00, 0x00, // lea rcx, [rip + 0xFF4]
+    ]);
+    let mut image = vec![0; 0x400 + text.len()];
+    let mut put =
+        |offset: usize, bytes: &[u8]| image[offset..offset + bytes.len()].copy_from_slice(bytes);
+    put(0, b"MZ");
+    put(0x3C, &0x80u32.to_le_bytes());
+    put(0x80, b"PE\0\0");
+    put(0x84, &0x8664u16.to_le_bytes());
+    put(0x86, &1u16.to_le_bytes());
+    put(0x88, &TIMESTAMP.to_le_bytes());
+    put(0x94, &0xF0u16.to_le_bytes());
+    put(0x98, &0x20Bu16.to_le_bytes());
+    put(0x98 + 0x18, &0x140000000u64.to_le_bytes());
+    put(0x98 + 0x38, &0x2000u32.to_le_bytes());
+    put(0x98 + 0x3C, &0x400u32.to_le_bytes());
+    put(0x98 + 0x6C, &16u32.to_le_bytes());
+    put(0x188, b".text");
+    put(0x188 + 0x8, &(text.len() as u32).to_le_bytes());
+    put(0x188 + 0xC, &0x1000u32.to_le_bytes());
+    put(0x188 + 0x10, &(text.len() as u32).to_le_bytes());
+    put(0x188 + 0x14, &0x400u32.to_le_bytes());
+    put(0x400, &text);
+    image
+}
+
+#[test]
+fn symbols_are_generated_from_signatures() {
+    let file = executable();
+    let image = PeImage::new(&file, ImageLayout::File).unwrap();
+    let signatures: Signatures = toml::from_str(
+        r#"
+        [symbols.start]
+        pattern = "E8 ?? ?? ?? ?? 48 8D 0D"
+
+        [symbols.callee]
+        pattern = "E8 ?? ?? ?? ?? 48 8D 0D"
+        operand = "branch"
+
+        [symbols.global]
+        pattern = "E8 ?? ?? ?? ?? 48 8D 0D"
+        offset = 5
+        operand = "rip"
+
+        [symbols.missing]
+        pattern = "90 90 90"
+        "#,
+    )
+    .unwrap();
+    let (symbols, errors) = generate(&image, &signatures).unwrap();
+    assert_eq!(
+        symbols.symbols().collect::<Vec<_>>(),
+        [("callee", 0x1100), ("global", 0x2000), ("start", 0x1000)]
+    );
+    // Only the symbols in `.text` have prologues, of whole instructions.
+    assert_eq!(
+        symbols.prologue("start").unwrap().to_string(),
+        "E8 FB 00 00 00 48 8D 0D F4 0F 00 00"
+    );
+    assert_eq!(
+        symbols.prologue("callee").unwrap().to_string(),
+        "CC CC CC CC CC CC CC CC"
+    );
+    assert_eq!(symbols.prologue("global"), None);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, "missing");
+    assert!(matches!(errors[0].1, LiberError::PatternNotFound(_)));
+
+    // The generated version is detected for the executable it was generated from.
+    let mut database = SymbolDatabase::default();
+    database.insert_version("1.0.0", symbols);
+    let database = SymbolDatabase::from_toml(&database.to_toml()).unwrap();
+    assert_eq!(
+        database.version_of(&GameBuild::of(&image).unwrap()),
+        Ok("1.0.0")
+    );
+    assert_eq!(database.rva("1.0.0", "global"), Ok(0x2000));
+}
+
+#[test]
+fn operands_must_exist() {
+    let file = executable();
+    let image = PeImage::new(&file, ImageLayout::File).unwrap();
+    let signatures: Signatures = toml::from_str(
+        r#"
+        [symbols.start]
+        pattern = "E8 ?? ?? ?? ?? 48 8D 0D"
+        operand = "rip"
+        "#,
+    )
+    .unwrap();
+    let (_, errors) = generate(&image, &signatures).unwrap();
+    assert_eq!(errors[0].1, LiberError::InvalidInstruction(0x1000));
+}
+
+#[test]
+fn signatures_are_checked() {
+    assert!(toml::from_str::<Signatures>("[symbols.a]\npatern = \"E8\"").is_err());
+    assert!(toml::from_str::<Signatures>("[symbols.a]\noperand = \"jump\"").is_err());
+
+    let file = executable();
+    let image = PeImage::new(&file, ImageLayout::File).unwrap();
+    let signatures: Signatures =
+        toml::from_str("[symbols.a]\npattern = \"E8\"\nrtti = \"CS::CSEzTask\"").unwrap();
+    let (_, errors) = generate(&image, &signatures).unwrap();
+    assert!(matches!(errors[0].1, LiberError::InvalidSymbolDatabase(_)));
+}
+
+#[test]
+fn arguments_are_parsed() {
+    let args = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+    let parsed = args(&[
+        "game.exe",
+        "sigs.toml",
+        "--version",
+        "1.2.0",
+        "--output",
+        "out.toml",
+    ])
+    .unwrap();
+    assert_eq!(parsed.executable.to_str(), Some("game.exe"));
+    assert_eq!(parsed.signatures.to_str(), Some("sigs.toml"));
+    assert_eq!(parsed.version, "1.2.0");
+    assert_eq!(parsed.output.unwrap().to_str(), Some("out.toml"));
+
+    assert!(args(&["game.exe", "sigs.toml"]).is_err());
+    assert!(args(&["game.exe", "--version", "1.2.0"]).is_err());
+    assert!(args(&["game.exe", "sigs.toml", "--version"]).is_err());
+    assert!(args(&["game.exe", "sigs.toml", "--version", "1", "--verbose"]).is_err());
+}
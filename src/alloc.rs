@@ -0,0 +1,68 @@
+use std::alloc::Layout;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// An allocator objects can be created in with `CppClass::new_in`, like one of the allocators of the game.
+///
+/// # Safety
+/// `allocate` must return null or memory that fits `layout`, which stays valid until it is passed to
+/// `deallocate`.
+pub unsafe trait CppAllocator: Send + Sync {
+    /// Allocate memory for `layout`. Returns null if the allocation failed.
+    fn allocate(&self, layout: Layout) -> *mut u8;
+    /// Free memory returned by `allocate` with the same `layout`.
+    ///
+    /// # Safety
+    /// `ptr` must have been returned by `allocate` of this allocator with `layout`, and not freed since.
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout);
+}
+
+/// The Rust global allocator, which `Box` uses. Objects that were not created with `CppClass::new_in` are
+/// freed with it.
+#[derive(Debug, Default)]
+pub struct RustAllocator;
+
+unsafe impl CppAllocator for RustAllocator {
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        unsafe { std::alloc::alloc(layout) }
+    }
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        std::alloc::dealloc(ptr, layout)
+    }
+}
+
+/// The allocators of the objects created with `CppClass::new_in`, by address, so they are freed with the same
+/// allocator.
+static ALLOCATIONS: Mutex<Option<HashMap<usize, &'static dyn CppAllocator>>> = Mutex::new(None);
+
+fn allocations<R>(f: impl FnOnce(&mut HashMap<usize, &'static dyn CppAllocator>) -> R) -> R {
+    let mut allocations = ALLOCATIONS.lock().unwrap_or_else(|e| e.into_inner());
+    f(allocations.get_or_insert_with(HashMap::new))
+}
+
+/// Allocate memory for a `T` with `allocator`, and remember the allocator for `free`.
+pub(crate) fn allocate<T>(allocator: &'static dyn CppAllocator) -> *mut T {
+    let ptr = allocator.allocate(Layout::new::<T>()) as *mut T;
+    if !ptr.is_null() {
+        allocations(|allocations| allocations.insert(ptr as usize, allocator));
+    }
+    ptr
+}
+
+/// Free the memory of the `T` at `ptr`, with the allocator it was allocated with by `allocate`, or with the
+/// Rust global allocator if it was not.
+///
+/// # Safety
+/// `ptr` must have been allocated by `allocate` or by `Box`, and its contents must already be dropped.
+pub(crate) unsafe fn free<T>(ptr: *mut T) {
+    let allocator = allocations(|allocations| allocations.remove(&(ptr as usize)));
+    allocator
+        .unwrap_or(&RustAllocator)
+        .deallocate(ptr as *mut u8, Layout::new::<T>());
+}
+
+/// Forget the allocator of the object at `ptr`, whose memory is freed by someone else, i.e. by the game through
+/// `DLRuntimeClass::free_base`.
+pub(crate) fn release<T>(ptr: *mut T) {
+    allocations(|allocations| allocations.remove(&(ptr as usize)));
+}
@@ -0,0 +1,116 @@
+use crate::from::CS::CSTaskGroup;
+use std::alloc::Layout;
+use std::fmt::{Display, Formatter};
+
+/// Errors of the APIs of this crate, so mods can handle a game that is not what they expect instead of crashing
+/// it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiberError {
+    /// The module provider did not find the module with this name, or the main executable if it is `None`.
+    ModuleNotFound(Option<String>),
+    /// A symbol of the game could not be resolved.
+    MissingSymbol(String),
+    /// A pointer that has to be set is null, i.e. the proxy of a task that is not registered.
+    NullPointer(&'static str),
+    /// The game version that is running is not supported.
+    UnsupportedVersion(String),
+    /// A task group tasks can not be registered at, like `CSTaskGroup::INVALID`.
+    InvalidTaskGroup(CSTaskGroup),
+    /// An allocator could not allocate memory for this layout.
+    AllocationFailed(Layout),
+    /// A symbol database could not be read or parsed.
+    InvalidSymbolDatabase(String),
+    /// A byte pattern could not be parsed.
+    InvalidPattern(String),
+    /// A byte pattern was not found.
+    PatternNotFound(String),
+    /// A byte pattern that has to be unique was found this many times.
+    PatternNotUnique(String, usize),
+    /// A PE image could not be parsed, or lacks a part that is needed.
+    InvalidImage(String),
+    /// The address cache could not be written.
+    AddressCache(String),
+    /// The instruction at this address could not be decoded, or has no operand of the kind that was asked for.
+    InvalidInstruction(usize),
+    /// A pointer chain could not be parsed.
+    InvalidPointerChain(String),
+    /// A pointer chain was null after following this many pointers.
+    NullPointerInChain(String, usize),
+    /// The memory at this address could not be read.
+    UnreadableMemory(usize),
+    /// The memory at this address could not be written.
+    UnwritableMemory(usize),
+    /// A function symbol is not in the `.text` section.
+    SymbolOutsideText(String),
+    /// A function symbol is inside a function, not at its start.
+    NotFunctionStart(String),
+    /// A function symbol does not start with the expected bytes.
+    PrologueMismatch(String),
+    /// A symbol is not the vtable of this class.
+    VTableMismatch(String, String),
+    /// These symbols failed validation.
+    InvalidSymbols(Vec<String>),
+    /// A function called by the game panicked on the object at this address, with this message.
+    Panicked(usize, String),
+}
+
+impl Display for LiberError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LiberError::ModuleNotFound(Some(name)) => write!(f, "module {name} is not loaded"),
+            LiberError::ModuleNotFound(None) => write!(f, "the main executable was not found"),
+            LiberError::MissingSymbol(name) => write!(f, "symbol {name} could not be resolved"),
+            LiberError::NullPointer(name) => write!(f, "{name} is null"),
+            LiberError::UnsupportedVersion(version) => {
+                write!(f, "game version {version} is not supported")
+            }
+            LiberError::InvalidTaskGroup(task_group) => {
+                write!(f, "tasks can not be registered at {task_group:?}")
+            }
+            LiberError::AllocationFailed(layout) => {
+                write!(f, "allocation of {} bytes failed", layout.size())
+            }
+            LiberError::InvalidSymbolDatabase(error) => {
+                write!(f, "invalid symbol database: {error}")
+            }
+            LiberError::InvalidPattern(pattern) => write!(f, "invalid pattern {pattern:?}"),
+            LiberError::PatternNotFound(pattern) => write!(f, "pattern {pattern} was not found"),
+            LiberError::PatternNotUnique(pattern, matches) => {
+                write!(f, "pattern {pattern} was found {matches} times")
+            }
+            LiberError::InvalidImage(error) => write!(f, "invalid PE image: {error}"),
+            LiberError::AddressCache(error) => write!(f, "address cache: {error}"),
+            LiberError::InvalidInstruction(address) => {
+                write!(f, "the instruction at {address:#x} can not be decoded")
+            }
+            LiberError::InvalidPointerChain(chain) => write!(f, "invalid pointer chain {chain:?}"),
+            LiberError::NullPointerInChain(chain, hop) => {
+                write!(f, "pointer {hop} of {chain} is null")
+            }
+            LiberError::UnreadableMemory(address) => {
+                write!(f, "the memory at {address:#x} can not be read")
+            }
+            LiberError::UnwritableMemory(address) => {
+                write!(f, "the memory at {address:#x} can not be written")
+            }
+            LiberError::SymbolOutsideText(name) => write!(f, "symbol {name} is not in .text"),
+            LiberError::NotFunctionStart(name) => {
+                write!(f, "symbol {name} is not at the start of a function")
+            }
+            LiberError::PrologueMismatch(name) => {
+                write!(f, "symbol {name} does not start with the expected bytes")
+            }
+            LiberError::VTableMismatch(name, class) => {
+                write!(f, "symbol {name} is not the vtable of {class}")
+            }
+            LiberError::InvalidSymbols(names) => {
+                write!(f, "symbols failed validation: {}", names.join(", "))
+            }
+            LiberError::Panicked(object, message) => {
+                write!(f, "panicked on {object:#x}: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LiberError {}
@@ -0,0 +1,255 @@
+use std::any::Any;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+
+use crate::LiberError;
+
+/// What to do after a panic was caught in a function called by the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicAction {
+    /// Return to the game as if the function completed.
+    Continue,
+    /// Return to the game, and stop executing the task the panic happened in. The task stays registered, but its
+    /// `execute` and `eztask_execute` are not called anymore.
+    DisableTask,
+    /// Abort the process.
+    Abort,
+}
+
+/// A panic caught in a function called by the game.
+#[derive(Debug)]
+pub struct PanicReport<'a> {
+    /// The function the panic happened in, i.e. `CSEzTask::eztask_execute`.
+    pub function: &'static str,
+    /// Address of the object the function was called on.
+    pub object: usize,
+    /// The panic message.
+    pub message: &'a str,
+}
+
+impl Display for PanicReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "panic in {} on {:#x}: {}",
+            self.function, self.object, self.message
+        )
+    }
+}
+
+/// An error in a function called by the game, which has no way to return it.
+#[derive(Debug)]
+pub struct ErrorReport<'a> {
+    /// The function the error happened in, i.e. `CSEzTask::~CSEzTask`.
+    pub function: &'static str,
+    /// The error.
+    pub error: &'a LiberError,
+}
+
+impl Display for ErrorReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} failed: {}", self.function, self.error)
+    }
+}
+
+type PanicHandler = Box<dyn Fn(&PanicReport) -> PanicAction + Send + Sync>;
+type ErrorHandler = Box<dyn Fn(&ErrorReport) + Send + Sync>;
+
+static PANIC_HANDLER: RwLock<Option<PanicHandler>> = RwLock::new(None);
+static ERROR_HANDLER: RwLock<Option<ErrorHandler>> = RwLock::new(None);
+
+/// The number of disabled objects that are checked without a lock.
+const DISABLED_SLOTS: usize = 64;
+
+/// Objects disabled after a panic. Every task checks whether it is disabled on every frame, and there are only
+/// a few at most, so they are kept in slots that are checked without a lock. Objects that do not fit go to a
+/// set, which is only locked while it is not empty.
+static DISABLED_OBJECTS: [AtomicUsize; DISABLED_SLOTS] =
+    [const { AtomicUsize::new(0) }; DISABLED_SLOTS];
+static MORE_DISABLED_OBJECTS: Mutex<Option<HashSet<usize>>> = Mutex::new(None);
+static MORE_DISABLED_LEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the handler for panics in functions called by the game, which is every function in the vtables of the
+/// classes implemented in Rust.
+///
+/// Unwinding into the game is undefined behavior, so these panics are caught. The handler decides what happens
+/// afterwards. The default handler reports the panic to the error handler as `LiberError::Panicked`, and
+/// disables the task.
+///
+/// If the function has to return a value, there is nothing to return after a panic, so the process is aborted
+/// regardless of the action.
+pub fn set_panic_handler(handler: impl Fn(&PanicReport) -> PanicAction + Send + Sync + 'static) {
+    *PANIC_HANDLER.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(handler));
+}
+
+fn default_panic_handler(report: &PanicReport) -> PanicAction {
+    let error = LiberError::Panicked(report.object, report.message.to_string());
+    report_error(report.function, &error);
+    PanicAction::DisableTask
+}
+
+/// Set the handler for errors in functions called by the game, which can not return them, like a task that
+/// could not be freed, or a panic the panic handler did not handle itself. Mods usually write them to their log.
+///
+/// The default handler writes them to the debugger output with `OutputDebugStringW` on Windows, where the game
+/// has no console, and to stderr elsewhere.
+pub fn set_error_handler(handler: impl Fn(&ErrorReport) + Send + Sync + 'static) {
+    *ERROR_HANDLER.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(handler));
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32", kind = "raw-dylib")]
+extern "system" {
+    fn OutputDebugStringW(message: *const u16);
+}
+
+#[cfg(windows)]
+fn default_error_handler(report: &ErrorReport) {
+    let message = widestring::U16CString::from_str_truncate(format!("liber-rs: {report}\n"));
+    unsafe { OutputDebugStringW(message.as_ptr()) };
+}
+
+#[cfg(not(windows))]
+fn default_error_handler(report: &ErrorReport) {
+    eprintln!("liber-rs: {report}");
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+fn more_disabled_objects<R>(f: impl FnOnce(&mut HashSet<usize>) -> R) -> R {
+    let mut objects = MORE_DISABLED_OBJECTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let objects = objects.get_or_insert_with(HashSet::new);
+    let result = f(objects);
+    MORE_DISABLED_LEN.store(objects.len(), Ordering::Release);
+    result
+}
+
+/// Disable the task at `object`, so it is not executed anymore.
+pub(crate) fn disable(object: usize) {
+    if object == 0 || is_disabled(object as *const ()) {
+        return;
+    }
+    let stored = DISABLED_OBJECTS.iter().any(|slot| {
+        slot.compare_exchange(0, object, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    });
+    if !stored {
+        more_disabled_objects(|objects| objects.insert(object));
+    }
+}
+
+/// Run `f` for `function`, called by the game on `this`, and hand a panic to the panic handler.
+///
+/// Every function in the vtables of this crate and of `#[derive(Inherit)]` classes does this. Use it for the
+/// functions of vtables written by hand as well.
+///
+/// Returns `None` if `f` panicked.
+pub fn guard<T: ?Sized, R>(
+    function: &'static str,
+    this: *const T,
+    f: impl FnOnce() -> R,
+) -> Option<R> {
+    let payload = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => return Some(result),
+        Err(payload) => payload,
+    };
+    let report = PanicReport {
+        function,
+        object: this as *const () as usize,
+        message: panic_message(payload.as_ref()),
+    };
+    let action = catch_unwind(|| {
+        match PANIC_HANDLER
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+        {
+            Some(handler) => handler(&report),
+            None => default_panic_handler(&report),
+        }
+    })
+    .unwrap_or(PanicAction::Abort);
+    match action {
+        PanicAction::Continue => {}
+        PanicAction::DisableTask => disable(report.object),
+        PanicAction::Abort => std::process::abort(),
+    }
+    None
+}
+
+/// Like `guard`, for functions that have to return a value to the game, so the process is aborted after a panic.
+pub fn guard_or_abort<T: ?Sized, R>(
+    function: &'static str,
+    this: *const T,
+    f: impl FnOnce() -> R,
+) -> R {
+    guard(function, this, f).unwrap_or_else(|| {
+        let object = this as *const () as usize;
+        let message = "there is no value to return to the game, aborting".to_string();
+        report_error(function, &LiberError::Panicked(object, message));
+        std::process::abort()
+    })
+}
+
+/// Like `guard`, for the functions that execute a task. They are skipped once the task has been disabled.
+pub fn guard_task<T: ?Sized>(function: &'static str, this: *const T, f: impl FnOnce()) {
+    if !is_disabled(this) {
+        guard(function, this, f);
+    }
+}
+
+/// Report an error in a function called by the game, which has no way to return it, to the error handler.
+pub(crate) fn report_error(function: &'static str, error: &LiberError) {
+    let report = ErrorReport { function, error };
+    // The function the error happened in was called by the game, so a panic in the handler can not unwind, and
+    // the error is dropped instead.
+    let _ = catch_unwind(|| {
+        match ERROR_HANDLER
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+        {
+            Some(handler) => handler(&report),
+            None => default_error_handler(&report),
+        }
+    });
+}
+
+/// Returns `true` if the task at `this` was disabled after a panic.
+pub fn is_disabled<T: ?Sized>(this: *const T) -> bool {
+    let object = this as *const () as usize;
+    object != 0
+        && (DISABLED_OBJECTS
+            .iter()
+            .any(|slot| slot.load(Ordering::Acquire) == object)
+            || MORE_DISABLED_LEN.load(Ordering::Acquire) != 0
+                && more_disabled_objects(|objects| objects.contains(&object)))
+}
+
+/// Forget that the object at `this` was disabled, once it is destroyed, so a new object at the same address is
+/// not disabled as well.
+pub fn forget<T: ?Sized>(this: *const T) {
+    let object = this as *const () as usize;
+    if object == 0 {
+        return;
+    }
+    for slot in &DISABLED_OBJECTS {
+        let _ = slot.compare_exchange(object, 0, Ordering::AcqRel, Ordering::Acquire);
+    }
+    if MORE_DISABLED_LEN.load(Ordering::Acquire) != 0 {
+        more_disabled_objects(|objects| objects.remove(&object));
+    }
+}
@@ -1,14 +1,20 @@
 use crate::from::CS::taskgroups::CSTaskGroup;
 use crate::from::DLRF::DLRuntimeClassType;
 use crate::from::FD4::{
-    DLRuntimeClassTrait, FD4ComponentBaseTrait, FD4TaskBase, FD4TaskBaseTrait, FD4TaskBaseType,
-    FD4TaskBaseVTable, FD4TaskData,
+    has_runtime_class, DLRuntimeClassTrait, FD4ComponentBaseTrait, FD4ComponentBaseType,
+    FD4ComponentBaseVTable, FD4TaskBase, FD4TaskBaseTrait, FD4TaskBaseType, FD4TaskBaseVTable,
+    FD4TaskBaseVirtual, FD4TaskData,
+};
+use crate::{
+    guard, guard_task, liber_fn, report_error, CppClass, CppLayout, CppPtr, FunctionSymbol,
+    Inherits, LiberError, RuntimeType, VTable, VTableLayout,
 };
-use crate::{get_base_address, CppClass, VTable};
 use cstr::cstr;
 use std::ffi::c_void;
+use std::marker::PhantomPinned;
 use std::ops::Deref;
-use widestring::widecstr;
+use std::pin::Pin;
+use widestring::u16cstr;
 
 /// Typedef of a special unsigned integer type that may represent a task id.
 ///
@@ -49,7 +55,7 @@ pub type CSEzTask = CppClass<CSEzTaskType>;
 const _: () = assert!(std::mem::size_of::<CSEzTask>() == 0x18);
 
 #[repr(C)]
-pub struct CSEzTaskVTable<C: VTable> {
+pub struct CSEzTaskVTable<C: VTableLayout> {
     fd4task_base_vtable: FD4TaskBaseVTable<C>,
     eztask_execute: EztaskExecuteFn<C>,
     register_task: RegisterTaskFn<C>,
@@ -57,7 +63,25 @@ pub struct CSEzTaskVTable<C: VTable> {
 }
 const _: () = assert!(std::mem::size_of::<CSEzTaskVTable<CSEzTaskType>>() == 0x30);
 
-impl<C: VTable> Deref for CSEzTaskVTable<C> {
+impl<C: VTableLayout> AsRef<FD4ComponentBaseVTable<C>> for CSEzTaskVTable<C> {
+    fn as_ref(&self) -> &FD4ComponentBaseVTable<C> {
+        self.fd4task_base_vtable.as_ref()
+    }
+}
+
+impl<C: VTableLayout> AsRef<FD4TaskBaseVTable<C>> for CSEzTaskVTable<C> {
+    fn as_ref(&self) -> &FD4TaskBaseVTable<C> {
+        &self.fd4task_base_vtable
+    }
+}
+
+impl<C: VTableLayout> AsRef<CSEzTaskVTable<C>> for CSEzTaskVTable<C> {
+    fn as_ref(&self) -> &CSEzTaskVTable<C> {
+        self
+    }
+}
+
+impl<C: VTableLayout> Deref for CSEzTaskVTable<C> {
     type Target = FD4TaskBaseVTable<C>;
 
     fn deref(&self) -> &Self::Target {
@@ -78,10 +102,14 @@ impl<C: VTable> Deref for CSEzTaskVTable<C> {
 /// is registered and executing. Use from::unique_ptr from from_unique_ptr.hpp to
 /// correctly manage its lifetime. Destroying it before it has executed on this
 /// pass will leave a dangling pointer in the task queue.
+///
+/// In Rust, tasks are `!Unpin`, and only pinned tasks can be registered, i.e. in a `Pin<Box<T>>` from
+/// `CSEzTaskTrait::pinned`, so a registered task can not be moved. Dropping a registered task frees it first.
 #[repr(C)]
 pub struct CSEzTaskType {
     fd4_task_base: FD4TaskBaseType,
     proxy: *mut CSEzTaskProxy,
+    _pinned: PhantomPinned,
 }
 const _: () = assert!(std::mem::size_of::<CSEzTaskType>() == 0x10);
 
@@ -90,16 +118,45 @@ impl CSEzTaskType {
         Self {
             fd4_task_base: Default::default(),
             proxy: std::ptr::null_mut(),
+            _pinned: PhantomPinned,
         }
     }
     pub fn get_proxy(&self) -> *mut CSEzTaskProxy {
         self.proxy
     }
-    pub fn set_proxy(&mut self, proxy: *mut CSEzTaskProxy) {
+    /// Set the proxy the game executes the task through, which marks the task as registered.
+    ///
+    /// # Safety
+    /// A task with a proxy is freed through its `CSEzTask` when it is dropped, so it has to be the data of a
+    /// `CSEzTask`, and `proxy` has to be null or stay valid as long as the task is registered.
+    pub unsafe fn set_proxy(&mut self, proxy: *mut CSEzTaskProxy) {
         self.proxy = proxy;
     }
-    pub fn get_task_group(&self) -> CSTaskGroup {
-        unsafe { (*self.proxy).task_group }
+    /// Get the task group the task was registered at, from its proxy.
+    pub fn get_task_group(&self) -> Result<CSTaskGroup, LiberError> {
+        match unsafe { self.proxy.as_ref() } {
+            Some(proxy) => Ok(proxy.task_group),
+            None => Err(LiberError::NullPointer("CSEzTask::proxy")),
+        }
+    }
+}
+
+impl Drop for CSEzTaskType {
+    /// Free the task if it is registered, like the destructor of `CSEzTask`, so the game does not execute it
+    /// after it is gone.
+    fn drop(&mut self) {
+        if self.proxy.is_null() {
+            return;
+        }
+        // Only the game and `set_proxy` set the proxy, on tasks that are the data of a `CSEzTask`.
+        let task = unsafe {
+            &*(self as *const Self)
+                .byte_sub(CSEzTask::DATA_OFFSET)
+                .cast::<CSEzTask>()
+        };
+        if let Err(error) = task.free_task() {
+            report_error("CSEzTask::~CSEzTask", &error);
+        }
     }
 }
 
@@ -117,75 +174,199 @@ impl Deref for CSEzTaskType {
     }
 }
 
-impl VTable for CSEzTaskType {
+impl VTableLayout for CSEzTaskType {
     type Table = CSEzTaskVTable<CSEzTaskType>;
+}
+
+impl VTable for CSEzTaskType {
     const TABLE: &'static Self::Table = &CSEzTaskVTable::new();
 }
 
-impl<C: VTable> CSEzTaskVTable<C>
+unsafe impl Inherits<FD4TaskBaseType> for CSEzTaskType {}
+unsafe impl Inherits<FD4ComponentBaseType> for CSEzTaskType {}
+
+impl RuntimeType for CSEzTaskType {
+    type Root = FD4ComponentBaseType;
+    fn is_instance(root: &CppClass<FD4ComponentBaseType>) -> bool {
+        has_runtime_class(root, CSEzTask::runtime_class())
+    }
+}
+
+impl<C: VTableLayout> CSEzTaskVTable<C>
 where
     CppClass<C>: CSEzTaskTrait,
 {
     pub const fn new() -> Self {
         Self {
             fd4task_base_vtable: FD4TaskBaseVTable::new(),
-            eztask_execute: <CppClass<C> as CSEzTaskTrait>::eztask_execute,
-            register_task: <CppClass<C> as CSEzTaskTrait>::register_task,
-            free_task: <CppClass<C> as CSEzTaskTrait>::free_task,
+            eztask_execute: eztask_execute_shim::<CppClass<C>>,
+            register_task: register_task_shim::<CppClass<C>>,
+            free_task: free_task_shim::<CppClass<C>>,
         }
     }
 }
 
+impl<C: VTableLayout> Default for CSEzTaskVTable<C>
+where
+    CppClass<C>: CSEzTaskTrait,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DLRuntimeClassTrait for CSEzTask {
-    extern "C" fn get_runtime_class(&self) -> &'static crate::from::DLRF::DLRuntimeClass {
+    fn runtime_class() -> &'static crate::from::DLRF::DLRuntimeClass {
         static DL_RUNTIME_CLASS: crate::from::DLRF::DLRuntimeClass =
-            crate::from::DLRF::DLRuntimeClass::from_data(DLRuntimeClassType::new(
+            crate::from::DLRF::DLRuntimeClass::from_data(DLRuntimeClassType::new::<CSEzTask>(
                 cstr!("CSEzTask"),
-                widecstr!("CSEzTask"),
+                u16cstr!("CSEzTask"),
             ));
         &DL_RUNTIME_CLASS
     }
 }
 
 impl FD4TaskBaseTrait for CSEzTask {
-    extern "C" fn execute(&self, data: &FD4TaskData) {
-        self.eztask_execute(data)
+    fn execute(&self, data: &FD4TaskData) {
+        self.virtual_eztask_execute(data)
     }
 }
 impl FD4ComponentBaseTrait for CSEzTask {
-    extern "C" fn destructor(&self) {
-        self.free_task();
-        unsafe { FD4TaskBase::destructor(&*(self as *const CSEzTask as *const FD4TaskBase)) };
+    /// The task is freed when `CSEzTaskType` is dropped, after this.
+    fn destructor(&self) {
+        FD4TaskBase::destructor(self.upcast());
     }
 }
 
+/// The symbol of `CS::CSEzTask::RegisterTask` in the symbol database.
+pub struct CSEzTaskRegisterTask;
+
+impl FunctionSymbol for CSEzTaskRegisterTask {
+    const NAME: &'static str = "CS::CSEzTask::register_task";
+    type Fn = extern "C" fn(*const CSEzTask, CSTaskGroup);
+}
+
+/// The symbol of `CS::CSEzTask::FreeTask` in the symbol database.
+pub struct CSEzTaskFreeTask;
+
+impl FunctionSymbol for CSEzTaskFreeTask {
+    const NAME: &'static str = "CS::CSEzTask::free_task";
+    type Fn = extern "C" fn(*const CSEzTask);
+}
+
+liber_fn! {
+    /// `CS::CSEzTask::RegisterTask` of the game.
+    unsafe fn cs_ez_task_register_task(this: *const CSEzTask, task_group: CSTaskGroup) = symbol(CSEzTaskRegisterTask);
+}
+
+liber_fn! {
+    /// `CS::CSEzTask::FreeTask` of the game.
+    unsafe fn cs_ez_task_free_task(this: *const CSEzTask) = symbol(CSEzTaskFreeTask);
+}
+
 pub trait CSEzTaskTrait: FD4TaskBaseTrait {
-    extern "C" fn eztask_execute(&self, data: &FD4TaskData);
-    extern "C" fn register_task(&self, task_group: CSTaskGroup) {
-        let register_task: extern "C" fn(_this: &Self, task_group: CSTaskGroup) =
-            unsafe { std::mem::transmute(get_base_address() + 0xE71C70) };
-        register_task(self, task_group)
+    fn eztask_execute(&self, data: &FD4TaskData);
+    /// Register the task to be executed in `task_group`. The task has to be pinned, as the game keeps a
+    /// pointer to it.
+    fn register_task(self: Pin<&Self>, task_group: CSTaskGroup) -> Result<(), LiberError> {
+        if !task_group.is_valid() {
+            return Err(LiberError::InvalidTaskGroup(task_group));
+        }
+        // The task is a `CSEzTask` that stays where it is while it is registered.
+        unsafe { cs_ez_task_register_task((self.get_ref() as *const Self).cast(), task_group) }
+    }
+    fn free_task(&self) -> Result<(), LiberError> {
+        unsafe { cs_ez_task_free_task((self as *const Self).cast()) }
+    }
+    /// Move the task to the heap, where it can be registered. It is freed when the box is dropped.
+    fn pinned(self) -> Pin<Box<Self>>
+    where
+        Self: Sized,
+    {
+        Box::pin(self)
+    }
+    /// Move the task to the heap for the rest of the process, for tasks that are registered once and never freed.
+    fn leaked(self) -> Pin<&'static Self>
+    where
+        Self: Sized,
+    {
+        Pin::static_ref(Box::leak(Box::new(self)))
+    }
+}
+// The functions in the vtable, which catch panics in the `CSEzTaskTrait` methods, see `guard`.
+pub extern "C" fn eztask_execute_shim<T: CSEzTaskTrait>(this: &T, data: &FD4TaskData) {
+    guard_task("CSEzTask::eztask_execute", this, || {
+        this.eztask_execute(data)
+    })
+}
+pub extern "C" fn register_task_shim<T: CSEzTaskTrait>(this: &T, task_group: CSTaskGroup) {
+    let function = "CSEzTask::register_task";
+    // The game only calls this on tasks it has a pointer to, which do not move.
+    let task = unsafe { Pin::new_unchecked(this) };
+    if let Some(Err(error)) = guard(function, this, || task.register_task(task_group)) {
+        report_error(function, &error);
+    }
+}
+pub extern "C" fn free_task_shim<T: CSEzTaskTrait>(this: &T) {
+    let function = "CSEzTask::free_task";
+    if let Some(Err(error)) = guard(function, this, || this.free_task()) {
+        report_error(function, &error);
+    }
+}
+
+/// Virtual calls to the `CSEzTask` methods of an object.
+///
+/// Unlike calling `CSEzTaskTrait` methods, these calls go through the vtable the object actually points to,
+/// so overrides installed by a subclass or by the game are honored.
+pub trait CSEzTaskVirtual: FD4TaskBaseVirtual {
+    fn virtual_eztask_execute(&self, data: &FD4TaskData);
+    /// Register the task through its vtable. Like `CSEzTaskTrait::register_task`, the task has to be pinned.
+    fn virtual_register_task(self: Pin<&Self>, task_group: CSTaskGroup);
+    fn virtual_free_task(&self);
+}
+
+impl<C: VTableLayout> CSEzTaskVirtual for CppClass<C>
+where
+    C::Table:
+        AsRef<CSEzTaskVTable<C>> + AsRef<FD4TaskBaseVTable<C>> + AsRef<FD4ComponentBaseVTable<C>>,
+{
+    fn virtual_eztask_execute(&self, data: &FD4TaskData) {
+        (AsRef::<CSEzTaskVTable<C>>::as_ref(self.vtable).eztask_execute)(self, data)
+    }
+    fn virtual_register_task(self: Pin<&Self>, task_group: CSTaskGroup) {
+        let this = self.get_ref();
+        (AsRef::<CSEzTaskVTable<C>>::as_ref(this.vtable).register_task)(this, task_group)
     }
-    extern "C" fn free_task(&self) {
-        let free_task: extern "C" fn(_this: &Self) =
-            unsafe { std::mem::transmute(get_base_address() + 0xE71D60) };
-        free_task(self)
+    fn virtual_free_task(&self) {
+        (AsRef::<CSEzTaskVTable<C>>::as_ref(self.vtable).free_task)(self)
     }
 }
+
 /// An internal proxy object for CS::CSEzTask instances inside ELDEN
 /// RING's task management system.
 /// This is just an example type.
 pub type CSEzTaskProxy = CppClass<CSEzTaskProxyType>;
-const _: () = assert!(std::mem::size_of::<CSEzTaskProxy>() == 0x20);
 /// `CSEzTaskProxy` vtable. This table currently serves as an example for the layout of the `CSEzTaskProxy`
 /// vtable.
 #[repr(C)]
-pub struct CSEzTaskProxyVTable<C: VTable> {
+pub struct CSEzTaskProxyVTable<C: VTableLayout> {
     pub fd4task_base_vtable: FD4TaskBaseVTable<C>,
 }
 const _: () = assert!(std::mem::size_of::<CSEzTaskVTable<CSEzTaskType>>() == 0x30);
 
-impl<C: VTable> Deref for CSEzTaskProxyVTable<C> {
+impl<C: VTableLayout> AsRef<FD4ComponentBaseVTable<C>> for CSEzTaskProxyVTable<C> {
+    fn as_ref(&self) -> &FD4ComponentBaseVTable<C> {
+        self.fd4task_base_vtable.as_ref()
+    }
+}
+
+impl<C: VTableLayout> AsRef<FD4TaskBaseVTable<C>> for CSEzTaskProxyVTable<C> {
+    fn as_ref(&self) -> &FD4TaskBaseVTable<C> {
+        &self.fd4task_base_vtable
+    }
+}
+
+impl<C: VTableLayout> Deref for CSEzTaskProxyVTable<C> {
     type Target = FD4TaskBaseVTable<C>;
 
     fn deref(&self) -> &Self::Target {
@@ -195,9 +376,15 @@ impl<C: VTable> Deref for CSEzTaskProxyVTable<C> {
 /// `CSEzTaskProxy` data type. This struct is expected to be the first struct in any user defined struct
 /// that is trying to inherit from `CSEzTaskProxy` (Currently unsupported).
 #[repr(C)]
+#[derive(CppLayout)]
+#[vtable]
+#[size(0x20)]
 pub struct CSEzTaskProxyType {
+    #[offset(0x8)]
     fd4_task_base: FD4TaskBaseType,
-    owner: *const CSEzTask,
+    #[offset(0x10)]
+    owner: CppPtr<CSEzTaskType>,
+    #[offset(0x18)]
     task_group: CSTaskGroup,
 }
 
@@ -205,15 +392,16 @@ impl CSEzTaskProxyType {
     pub fn new(task_group: CSTaskGroup) -> Self {
         Self {
             fd4_task_base: Default::default(),
-            owner: std::ptr::null(),
+            owner: CppPtr::null(),
             task_group,
         }
     }
-    pub fn owner(&self) -> *const CSEzTask {
+    /// The task this proxy executes. This is usually a task created by the game, or a subclass of `CSEzTask`.
+    pub fn owner(&self) -> CppPtr<CSEzTaskType> {
         self.owner
     }
-    pub fn set_owner(&mut self, task: *const CSEzTask) {
-        self.owner = task;
+    pub fn set_owner(&mut self, task: impl Into<CppPtr<CSEzTaskType>>) {
+        self.owner = task.into();
     }
 }
 impl Default for CSEzTaskProxyType {
@@ -222,7 +410,7 @@ impl Default for CSEzTaskProxyType {
     }
 }
 
-impl<C: VTable> CSEzTaskProxyVTable<C>
+impl<C: VTableLayout> CSEzTaskProxyVTable<C>
 where
     CppClass<C>: FD4TaskBaseTrait,
 {
@@ -233,14 +421,25 @@ where
     }
 }
 
+impl<C: VTableLayout> Default for CSEzTaskProxyVTable<C>
+where
+    CppClass<C>: FD4TaskBaseTrait,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FD4TaskBaseTrait for CSEzTaskProxy {
     /// Executes the proxied task.
     ///
     /// # Arguments
     ///
     /// * `data`: a struct with additional data passed to the task
-    extern "C" fn execute(&self, data: &FD4TaskData) {
-        unsafe { (*self.owner).eztask_execute(data) }
+    fn execute(&self, data: &FD4TaskData) {
+        if let Some(owner) = unsafe { self.owner.as_ref() } {
+            owner.virtual_eztask_execute(data)
+        }
     }
 }
 impl CSEzTaskProxy {
@@ -255,21 +454,34 @@ impl CSEzTaskProxy {
 impl FD4ComponentBaseTrait for CSEzTaskProxy {}
 
 impl DLRuntimeClassTrait for CSEzTaskProxy {
-    extern "C" fn get_runtime_class(&self) -> &'static crate::from::DLRF::DLRuntimeClass {
+    fn runtime_class() -> &'static crate::from::DLRF::DLRuntimeClass {
         static DL_RUNTIME_CLASS: crate::from::DLRF::DLRuntimeClass =
-            crate::from::DLRF::DLRuntimeClass::from_data(DLRuntimeClassType::new(
+            crate::from::DLRF::DLRuntimeClass::from_data(DLRuntimeClassType::new::<CSEzTaskProxy>(
                 cstr!("CSEzTaskProxy"),
-                widecstr!("CSEzTaskProxy"),
+                u16cstr!("CSEzTaskProxy"),
             ));
         &DL_RUNTIME_CLASS
     }
 }
 impl CSEzTaskTrait for CSEzTask {
-    extern "C" fn eztask_execute(&self, _data: &FD4TaskData) {}
+    fn eztask_execute(&self, _data: &FD4TaskData) {}
 }
 
-impl VTable for CSEzTaskProxyType {
+unsafe impl Inherits<FD4TaskBaseType> for CSEzTaskProxyType {}
+unsafe impl Inherits<FD4ComponentBaseType> for CSEzTaskProxyType {}
+
+impl RuntimeType for CSEzTaskProxyType {
+    type Root = FD4ComponentBaseType;
+    fn is_instance(root: &CppClass<FD4ComponentBaseType>) -> bool {
+        has_runtime_class(root, CSEzTaskProxy::runtime_class())
+    }
+}
+
+impl VTableLayout for CSEzTaskProxyType {
     type Table = CSEzTaskProxyVTable<CSEzTaskProxyType>;
+}
+
+impl VTable for CSEzTaskProxyType {
     const TABLE: &'static Self::Table = &CSEzTaskProxyVTable::new();
 }
 /// A child task executed by tasks and steppers in ELDEN RING.
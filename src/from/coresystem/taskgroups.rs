@@ -1,7 +1,9 @@
+/// The task groups of ELDEN RING, in the order they are executed in every frame.
+#[cfg(feature = "eldenring")]
 #[repr(i32)]
 #[allow(non_camel_case_types)]
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum CSTaskGroup {
     INVALID = -1,
     FrameBegin = 0,
@@ -176,3 +178,31 @@ pub enum CSTaskGroup {
     SIZE,
 }
 const _: () = assert!(std::mem::size_of::<CSTaskGroup>() == 0x4);
+
+#[cfg(feature = "eldenring")]
+impl CSTaskGroup {
+    /// Returns `true` if tasks can be registered at this task group.
+    pub fn is_valid(self) -> bool {
+        !matches!(self, CSTaskGroup::INVALID | CSTaskGroup::SIZE)
+    }
+}
+
+/// A task group of a game whose task groups are not listed yet, by its index in the game.
+#[cfg(not(feature = "eldenring"))]
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CSTaskGroup(pub i32);
+
+#[cfg(not(feature = "eldenring"))]
+#[allow(non_upper_case_globals)]
+impl CSTaskGroup {
+    pub const INVALID: Self = Self(-1);
+    /// The first task group of every frame.
+    pub const FrameBegin: Self = Self(0);
+
+    /// Returns `true` if tasks can be registered at this task group. The number of task groups is not known,
+    /// so only negative indices are rejected.
+    pub fn is_valid(self) -> bool {
+        self.0 >= 0
+    }
+}
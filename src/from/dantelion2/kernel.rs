@@ -0,0 +1,72 @@
+use crate::{CppAllocator, CppClass, VTableLayout};
+use std::alloc::Layout;
+
+pub type AllocateFn = extern "C" fn(&DLAllocator, size: usize) -> *mut u8;
+pub type AllocateAlignedFn = extern "C" fn(&DLAllocator, size: usize, alignment: usize) -> *mut u8;
+pub type ReallocateFn = extern "C" fn(&DLAllocator, ptr: *mut u8, size: usize) -> *mut u8;
+pub type ReallocateAlignedFn =
+    extern "C" fn(&DLAllocator, ptr: *mut u8, size: usize, alignment: usize) -> *mut u8;
+pub type FreeFn = extern "C" fn(&DLAllocator, ptr: *mut u8);
+
+/// An allocator of the game, like the ones objects of the game are created in and freed with.
+pub type DLAllocator = CppClass<DLAllocatorType>;
+
+#[repr(C)]
+pub struct DLAllocatorVTable {
+    /// The destructor, and the queries of the id, the flags, the capacity and the statistics of the heap, which
+    /// liber-rs does not call.
+    pub(crate) _unk: [usize; 9],
+    pub(crate) allocate: AllocateFn,
+    pub(crate) allocate_aligned: AllocateAlignedFn,
+    pub(crate) reallocate: ReallocateFn,
+    pub(crate) reallocate_aligned: ReallocateAlignedFn,
+    pub(crate) free: FreeFn,
+}
+const _: () = assert!(std::mem::size_of::<DLAllocatorVTable>() == 0x70);
+
+/// The allocators are only ever created by the game, so the class has no data or vtable implementation in Rust.
+#[repr(C)]
+pub struct DLAllocatorType;
+
+impl VTableLayout for DLAllocatorType {
+    type Table = DLAllocatorVTable;
+}
+
+impl DLAllocator {
+    pub fn allocate(&self, size: usize) -> *mut u8 {
+        (self.vtable.allocate)(self, size)
+    }
+    pub fn allocate_aligned(&self, size: usize, alignment: usize) -> *mut u8 {
+        (self.vtable.allocate_aligned)(self, size, alignment)
+    }
+    /// # Safety
+    /// `ptr` must be null or have been allocated by this allocator, and not be used after this call.
+    pub unsafe fn reallocate(&self, ptr: *mut u8, size: usize) -> *mut u8 {
+        (self.vtable.reallocate)(self, ptr, size)
+    }
+    /// # Safety
+    /// See `reallocate`.
+    pub unsafe fn reallocate_aligned(
+        &self,
+        ptr: *mut u8,
+        size: usize,
+        alignment: usize,
+    ) -> *mut u8 {
+        (self.vtable.reallocate_aligned)(self, ptr, size, alignment)
+    }
+    /// # Safety
+    /// `ptr` must be null or have been allocated by this allocator, and not be used after this call.
+    pub unsafe fn free(&self, ptr: *mut u8) {
+        (self.vtable.free)(self, ptr)
+    }
+}
+
+/// Objects can be created in an allocator of the game with `CppClass::new_in`, so the game can free them itself.
+unsafe impl CppAllocator for DLAllocator {
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        self.allocate_aligned(layout.size(), layout.align())
+    }
+    unsafe fn deallocate(&self, ptr: *mut u8, _layout: Layout) {
+        self.free(ptr)
+    }
+}
@@ -1,17 +1,21 @@
-use crate::{CppClass, VTable};
+use crate::from::DLKR::DLAllocator;
+use crate::from::FD4::{FD4ComponentBase, FD4ComponentBaseVirtual};
+use crate::{alloc, guard, guard_or_abort, CppClass, VTable, VTableLayout};
 use std::ffi::{c_char, CStr};
-use widestring::WideCStr;
+use std::sync::atomic::AtomicU8;
+use widestring::U16CStr;
 
 pub type ClassNameFn<C> = extern "C" fn(&CppClass<C>) -> &'static c_char;
 pub type ClassNameWFn<C> = extern "C" fn(&CppClass<C>) -> &'static u16;
-pub type RefByteFn<C> = extern "C" fn(&CppClass<C>) -> &'static u8;
+pub type RefByteFn<C> = extern "C" fn(&CppClass<C>) -> &AtomicU8;
 pub type UnkAlwaysFalseFn<C> = extern "C" fn(&CppClass<C>) -> bool;
-pub type FreeBaseFn<C> = extern "C" fn(&&CppClass<C>, allocator: *const u8);
+pub type FreeBaseFn<C> =
+    unsafe extern "C" fn(&CppClass<C>, base: &mut *mut FD4ComponentBase, allocator: &DLAllocator);
 pub type ClassSizeFn<C> = extern "C" fn(&CppClass<C>) -> usize;
 /// The concatenated C++ class that represents
 pub type DLRuntimeClass = CppClass<DLRuntimeClassType>;
 #[repr(C)]
-pub struct DLRuntimeClassVTable<C: VTable> {
+pub struct DLRuntimeClassVTable<C: VTableLayout> {
     class_name: ClassNameFn<C>,
     class_name_w: ClassNameWFn<C>,
     ref_byte1: RefByteFn<C>,
@@ -24,93 +28,244 @@ pub struct DLRuntimeClassVTable<C: VTable> {
 }
 const _: () = assert!(std::mem::size_of::<DLRuntimeClassVTable<DLRuntimeClassType>>() == 0x48);
 
-impl<C: VTable> DLRuntimeClassVTable<C>
+impl<C: VTableLayout> DLRuntimeClassVTable<C>
 where
     CppClass<C>: DLRuntimeClassTrait,
 {
     pub const fn new() -> Self {
         Self {
-            class_name: <CppClass<C> as DLRuntimeClassTrait>::class_name,
-            class_name_w: <CppClass<C> as DLRuntimeClassTrait>::class_name_w,
-            ref_byte1: <CppClass<C> as DLRuntimeClassTrait>::ref_byte1,
-            ref_byte2: <CppClass<C> as DLRuntimeClassTrait>::ref_byte2,
-            ref_byte3: <CppClass<C> as DLRuntimeClassTrait>::ref_byte3,
-            ref_byte4: <CppClass<C> as DLRuntimeClassTrait>::ref_byte4,
-            unk_always_false: <CppClass<C> as DLRuntimeClassTrait>::unk_always_false,
-            free_base: <CppClass<C> as DLRuntimeClassTrait>::free_base,
-            class_size: <CppClass<C> as DLRuntimeClassTrait>::class_size,
+            class_name: class_name_shim::<CppClass<C>>,
+            class_name_w: class_name_w_shim::<CppClass<C>>,
+            ref_byte1: ref_byte1_shim::<CppClass<C>>,
+            ref_byte2: ref_byte2_shim::<CppClass<C>>,
+            ref_byte3: ref_byte3_shim::<CppClass<C>>,
+            ref_byte4: ref_byte4_shim::<CppClass<C>>,
+            unk_always_false: unk_always_false_shim::<CppClass<C>>,
+            free_base: free_base_shim::<CppClass<C>>,
+            class_size: class_size_shim::<CppClass<C>>,
         }
     }
 }
 
+impl<C: VTableLayout> Default for DLRuntimeClassVTable<C>
+where
+    CppClass<C>: DLRuntimeClassTrait,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: VTableLayout> AsRef<DLRuntimeClassVTable<C>> for DLRuntimeClassVTable<C> {
+    fn as_ref(&self) -> &DLRuntimeClassVTable<C> {
+        self
+    }
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct DLRuntimeClassType {
     _class_name: &'static c_char,
     _class_name_w: &'static u16,
+    /// Size of an object of the described class.
+    class_size: usize,
+    /// The bytes the game keeps for the class, see `DLRuntimeClassTrait::ref_byte1`.
+    ref_bytes: [AtomicU8; 4],
 }
-const _: () = assert!(std::mem::size_of::<DLRuntimeClassType>() == 0x10);
+const _: () = assert!(std::mem::size_of::<DLRuntimeClassType>() == 0x20);
 
-impl VTable for DLRuntimeClassType {
+impl VTableLayout for DLRuntimeClassType {
     type Table = DLRuntimeClassVTable<DLRuntimeClassType>;
+}
+
+impl VTable for DLRuntimeClassType {
     const TABLE: &'static Self::Table = &DLRuntimeClassVTable::new();
 }
 
 impl DLRuntimeClassType {
-    pub const fn new(
+    /// The runtime class of `T`. Every class needs a runtime class of its own, see `runtime_class`.
+    pub const fn new<T>(
         class_name: &'static CStr,
-        class_name_w: &'static WideCStr,
+        class_name_w: &'static U16CStr,
     ) -> DLRuntimeClassType {
         unsafe {
             Self {
                 _class_name: &*class_name.as_ptr(),
                 _class_name_w: &*class_name_w.as_ptr(),
+                class_size: std::mem::size_of::<T>(),
+                ref_bytes: [
+                    AtomicU8::new(0),
+                    AtomicU8::new(0),
+                    AtomicU8::new(0),
+                    AtomicU8::new(0),
+                ],
             }
         }
     }
 }
 
+impl DLRuntimeClass {
+    /// Name of the class, read through the vtable so it works for the runtime classes of the game as well.
+    pub fn name(&self) -> &'static CStr {
+        unsafe { CStr::from_ptr(self.virtual_class_name()) }
+    }
+    /// Checks if both runtime classes describe the same class. Every class has a runtime class of its own,
+    /// so they are compared by address. Names are not unique, i.e. classes of the same name in different
+    /// modules of a mod are different classes.
+    pub fn is_same_class(&self, other: &DLRuntimeClass) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
 pub trait DLRuntimeClassTrait {
-    extern "C" fn class_name(&self) -> &'static c_char;
-    extern "C" fn class_name_w(&self) -> &'static u16;
-    extern "C" fn ref_byte1(&self) -> &'static u8;
-    extern "C" fn ref_byte2(&self) -> &'static u8;
-    extern "C" fn ref_byte3(&self) -> &'static u8;
-    extern "C" fn ref_byte4(&self) -> &'static u8;
-    extern "C" fn unk_always_false(&self) -> bool;
-    extern "C" fn free_base(this: &&Self, allocator: *const u8);
-    extern "C" fn class_size(&self) -> usize;
+    fn class_name(&self) -> &'static c_char;
+    fn class_name_w(&self) -> &'static u16;
+    /// A byte the game keeps for the class, which it reads and writes through the returned reference. Every
+    /// class has its own four of them, which start out as 0.
+    fn ref_byte1(&self) -> &AtomicU8;
+    fn ref_byte2(&self) -> &AtomicU8;
+    fn ref_byte3(&self) -> &AtomicU8;
+    fn ref_byte4(&self) -> &AtomicU8;
+    /// `false` for every runtime class of the game.
+    fn unk_always_false(&self) -> bool;
+    /// Destroy the object of the class `base` points to, free its memory with `allocator`, and set `base` to
+    /// null. Nothing happens if `base` is null already.
+    ///
+    /// # Safety
+    /// `base` must be null or point to a live object of the class, which was allocated with `allocator` and is
+    /// not used after this call.
+    unsafe fn free_base(&self, base: &mut *mut FD4ComponentBase, allocator: &DLAllocator);
+    /// Size of an object of the class.
+    fn class_size(&self) -> usize;
 }
 
 impl DLRuntimeClassTrait for DLRuntimeClass {
-    extern "C" fn class_name(&self) -> &'static c_char {
+    fn class_name(&self) -> &'static c_char {
         self._class_name
     }
-    extern "C" fn class_name_w(&self) -> &'static u16 {
+    fn class_name_w(&self) -> &'static u16 {
         self._class_name_w
     }
-    extern "C" fn ref_byte1(&self) -> &'static u8 {
-        &0
+    fn ref_byte1(&self) -> &AtomicU8 {
+        &self.ref_bytes[0]
     }
-    extern "C" fn ref_byte2(&self) -> &'static u8 {
-        &0
+    fn ref_byte2(&self) -> &AtomicU8 {
+        &self.ref_bytes[1]
     }
-    extern "C" fn ref_byte3(&self) -> &'static u8 {
-        &0
+    fn ref_byte3(&self) -> &AtomicU8 {
+        &self.ref_bytes[2]
     }
-    extern "C" fn ref_byte4(&self) -> &'static u8 {
-        &0
+    fn ref_byte4(&self) -> &AtomicU8 {
+        &self.ref_bytes[3]
     }
 
-    extern "C" fn unk_always_false(&self) -> bool {
+    fn unk_always_false(&self) -> bool {
         false
     }
 
-    extern "C" fn free_base(this: &&Self, allocator: *const u8) {
-        todo!("{this:?} {allocator:?}")
+    unsafe fn free_base(&self, base: &mut *mut FD4ComponentBase, allocator: &DLAllocator) {
+        let object = std::mem::replace(base, std::ptr::null_mut());
+        if object.is_null() {
+            return;
+        }
+        // Destroyed without the delete flag, as the memory is freed with `allocator` rather than the allocator
+        // the object was created in.
+        FD4ComponentBase::virtual_destructor(object, 0);
+        alloc::release(object);
+        allocator.free(object.cast());
     }
 
-    extern "C" fn class_size(&self) -> usize {
-        std::mem::size_of::<Self>()
+    fn class_size(&self) -> usize {
+        self.class_size
+    }
+}
+
+// The functions in the vtable, which catch panics in the `DLRuntimeClassTrait` methods, see `guard`.
+pub extern "C" fn class_name_shim<T: DLRuntimeClassTrait>(this: &T) -> &'static c_char {
+    guard_or_abort("DLRuntimeClass::class_name", this, || this.class_name())
+}
+pub extern "C" fn class_name_w_shim<T: DLRuntimeClassTrait>(this: &T) -> &'static u16 {
+    guard_or_abort("DLRuntimeClass::class_name_w", this, || this.class_name_w())
+}
+pub extern "C" fn ref_byte1_shim<T: DLRuntimeClassTrait>(this: &T) -> &AtomicU8 {
+    guard_or_abort("DLRuntimeClass::ref_byte1", this, || this.ref_byte1())
+}
+pub extern "C" fn ref_byte2_shim<T: DLRuntimeClassTrait>(this: &T) -> &AtomicU8 {
+    guard_or_abort("DLRuntimeClass::ref_byte2", this, || this.ref_byte2())
+}
+pub extern "C" fn ref_byte3_shim<T: DLRuntimeClassTrait>(this: &T) -> &AtomicU8 {
+    guard_or_abort("DLRuntimeClass::ref_byte3", this, || this.ref_byte3())
+}
+pub extern "C" fn ref_byte4_shim<T: DLRuntimeClassTrait>(this: &T) -> &AtomicU8 {
+    guard_or_abort("DLRuntimeClass::ref_byte4", this, || this.ref_byte4())
+}
+pub extern "C" fn unk_always_false_shim<T: DLRuntimeClassTrait>(this: &T) -> bool {
+    guard_or_abort("DLRuntimeClass::unk_always_false", this, || {
+        this.unk_always_false()
+    })
+}
+/// # Safety
+/// See `DLRuntimeClassTrait::free_base`.
+pub unsafe extern "C" fn free_base_shim<T: DLRuntimeClassTrait>(
+    this: &T,
+    base: &mut *mut FD4ComponentBase,
+    allocator: &DLAllocator,
+) {
+    guard("DLRuntimeClass::free_base", this, || {
+        this.free_base(base, allocator)
+    });
+}
+pub extern "C" fn class_size_shim<T: DLRuntimeClassTrait>(this: &T) -> usize {
+    guard_or_abort("DLRuntimeClass::class_size", this, || this.class_size())
+}
+
+/// Virtual calls to the `DLRuntimeClass` methods of an object.
+///
+/// Unlike calling `DLRuntimeClassTrait` methods, these calls go through the vtable the object actually points
+/// to, so the runtime classes of the game can be read the same way as the ones defined in Rust.
+pub trait DLRuntimeClassVirtual {
+    fn virtual_class_name(&self) -> &'static c_char;
+    fn virtual_class_name_w(&self) -> &'static u16;
+    fn virtual_ref_byte1(&self) -> &AtomicU8;
+    fn virtual_ref_byte2(&self) -> &AtomicU8;
+    fn virtual_ref_byte3(&self) -> &AtomicU8;
+    fn virtual_ref_byte4(&self) -> &AtomicU8;
+    fn virtual_unk_always_false(&self) -> bool;
+    /// # Safety
+    /// See `DLRuntimeClassTrait::free_base`.
+    unsafe fn virtual_free_base(&self, base: &mut *mut FD4ComponentBase, allocator: &DLAllocator);
+    fn virtual_class_size(&self) -> usize;
+}
+
+impl<C: VTableLayout> DLRuntimeClassVirtual for CppClass<C>
+where
+    C::Table: AsRef<DLRuntimeClassVTable<C>>,
+{
+    fn virtual_class_name(&self) -> &'static c_char {
+        (self.vtable.as_ref().class_name)(self)
+    }
+    fn virtual_class_name_w(&self) -> &'static u16 {
+        (self.vtable.as_ref().class_name_w)(self)
+    }
+    fn virtual_ref_byte1(&self) -> &AtomicU8 {
+        (self.vtable.as_ref().ref_byte1)(self)
+    }
+    fn virtual_ref_byte2(&self) -> &AtomicU8 {
+        (self.vtable.as_ref().ref_byte2)(self)
+    }
+    fn virtual_ref_byte3(&self) -> &AtomicU8 {
+        (self.vtable.as_ref().ref_byte3)(self)
+    }
+    fn virtual_ref_byte4(&self) -> &AtomicU8 {
+        (self.vtable.as_ref().ref_byte4)(self)
+    }
+    fn virtual_unk_always_false(&self) -> bool {
+        (self.vtable.as_ref().unk_always_false)(self)
+    }
+    unsafe fn virtual_free_base(&self, base: &mut *mut FD4ComponentBase, allocator: &DLAllocator) {
+        (self.vtable.as_ref().free_base)(self, base, allocator)
+    }
+    fn virtual_class_size(&self) -> usize {
+        (self.vtable.as_ref().class_size)(self)
     }
 }
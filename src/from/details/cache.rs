@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::from::details::module::current_module_path;
+use crate::from::details::pe::PeImage;
+use crate::{report_error, LiberError};
+
+/// The file name of the address cache, next to the module of the mod.
+const CACHE_FILE_NAME: &str = "liber-address-cache.toml";
+
+/// RVAs found by pattern scans and RTTI walks, saved to a file so they do not have to be searched for again on
+/// the next launch.
+///
+/// The cache is keyed by a hash of the headers of the executable, which hold its link timestamp, checksum and
+/// section table, so it is dropped when the game is updated. The rest of the image is not hashed, as it differs
+/// between launches once it is loaded, with relocations applied for its base address and its data written to.
+/// Every cached RVA is validated before it is used, and searched for again if it is not valid anymore.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressCache {
+    key: String,
+    addresses: BTreeMap<String, u32>,
+}
+
+impl AddressCache {
+    /// An empty cache for `image`.
+    pub fn new(image: &PeImage) -> Self {
+        Self {
+            key: image_key(image),
+            addresses: BTreeMap::new(),
+        }
+    }
+    /// Read the cache at `path`. The cache is empty if the file does not exist, can not be parsed, or was
+    /// written for another executable.
+    pub fn load(path: &Path, image: &PeImage) -> Self {
+        let key = image_key(image);
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|toml| toml::from_str::<Self>(&toml).ok())
+            .filter(|cache| cache.key == key)
+            .unwrap_or_else(|| Self::new(image))
+    }
+    /// Write the cache to `path`.
+    pub fn save(&self, path: &Path) -> Result<(), LiberError> {
+        let toml = toml::to_string(self).map_err(|e| LiberError::AddressCache(e.to_string()))?;
+        std::fs::write(path, toml)
+            .map_err(|e| LiberError::AddressCache(format!("{}: {e}", path.display())))
+    }
+    /// The cached RVA of `name`, without validating it.
+    pub fn get(&self, name: &str) -> Option<u32> {
+        self.addresses.get(name).copied()
+    }
+    /// The cached RVA of `name` in `image`, if `validate` accepts it.
+    pub fn get_valid(
+        &self,
+        image: &PeImage,
+        name: &str,
+        validate: impl Fn(u32) -> bool,
+    ) -> Option<u32> {
+        self.get(name)
+            .filter(|&rva| rva < image.size_of_image() && validate(rva))
+    }
+    /// Cache `rva` as the RVA of `name`.
+    pub fn insert(&mut self, name: impl Into<String>, rva: u32) {
+        self.addresses.insert(name.into(), rva);
+    }
+    /// The RVA of `name` in `image`. A cached RVA is used if `validate` accepts it, otherwise it is found with
+    /// `resolve` and cached.
+    ///
+    /// Returns the RVA, and whether the cache changed.
+    pub fn rva(
+        &mut self,
+        image: &PeImage,
+        name: &str,
+        validate: impl Fn(u32) -> bool,
+        resolve: impl FnOnce() -> Result<u32, LiberError>,
+    ) -> Result<(u32, bool), LiberError> {
+        if let Some(rva) = self.get_valid(image, name, validate) {
+            return Ok((rva, false));
+        }
+        let rva = resolve()?;
+        self.insert(name, rva);
+        Ok((rva, true))
+    }
+}
+
+/// FNV-1a over the headers of the image, which are the same in the file and once it is loaded.
+fn image_key(image: &PeImage) -> String {
+    let headers = &image.data()[..(image.size_of_headers() as usize).min(image.data().len())];
+    let hash = headers.iter().fold(0xCBF29CE484222325u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001B3)
+    });
+    format!("{hash:016x}")
+}
+
+struct GameCache {
+    path: Option<PathBuf>,
+    cache: Option<AddressCache>,
+}
+
+static CACHE: Mutex<GameCache> = Mutex::new(GameCache {
+    path: None,
+    cache: None,
+});
+
+/// Set the file the addresses found in the game are cached in. It defaults to `liber-address-cache.toml` next
+/// to the module of the mod.
+pub fn set_address_cache_path(path: impl Into<PathBuf>) {
+    *CACHE.lock().unwrap_or_else(|e| e.into_inner()) = GameCache {
+        path: Some(path.into()),
+        cache: None,
+    };
+}
+
+/// The file the addresses found in the game are cached in.
+pub fn address_cache_path() -> PathBuf {
+    let cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.path.clone().unwrap_or_else(default_cache_path)
+}
+
+fn default_cache_path() -> PathBuf {
+    current_module_path()
+        .and_then(|path| Some(path.parent()?.join(CACHE_FILE_NAME)))
+        .unwrap_or_else(|| PathBuf::from(CACHE_FILE_NAME))
+}
+
+impl GameCache {
+    /// The cache of `image`, which is loaded the first time, and the file it is saved in.
+    fn load(&mut self, image: &PeImage) -> (&mut AddressCache, PathBuf) {
+        let path = self.path.clone().unwrap_or_else(default_cache_path);
+        let cache = self
+            .cache
+            .get_or_insert_with(|| AddressCache::load(&path, image));
+        (cache, path)
+    }
+}
+
+/// The RVA of `name` in the game executable `image`, through the address cache. See `AddressCache::rva`.
+///
+/// The cache is not locked while the RVA is validated or resolved, so a pattern scan does not hold up other
+/// lookups.
+pub(crate) fn cached_game_rva(
+    image: &PeImage<'static>,
+    name: &str,
+    validate: impl Fn(u32) -> bool,
+    resolve: impl FnOnce() -> Result<u32, LiberError>,
+) -> Result<u32, LiberError> {
+    let cached = {
+        let mut game = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        game.load(image).0.get(name)
+    };
+    if let Some(rva) = cached.filter(|&rva| rva < image.size_of_image() && validate(rva)) {
+        return Ok(rva);
+    }
+    let rva = resolve()?;
+    let mut game = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let (cache, path) = game.load(image);
+    cache.insert(name, rva);
+    if let Err(error) = cache.save(&path) {
+        report_error("AddressCache::save", &error);
+    }
+    Ok(rva)
+}
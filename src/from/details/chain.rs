@@ -0,0 +1,339 @@
+use std::fmt::{Display, Formatter};
+use std::mem::MaybeUninit;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::from::details::memory::{Memory, ProcessMemory};
+use crate::{module_provider, LiberError};
+
+/// A type that can be read from any bytes, so it can be read from game memory at the end of a
+/// `PointerChain`.
+///
+/// # Safety
+/// Every bit pattern of the size of the type has to be a valid value of it, so it can not contain references,
+/// `bool`s or enums.
+pub unsafe trait Plain: Copy {}
+
+macro_rules! plain {
+    ($($ty:ty),*) => {
+        $(unsafe impl Plain for $ty {})*
+    };
+}
+
+plain!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+unsafe impl<T: Plain, const N: usize> Plain for [T; N] {}
+
+/// Where a `PointerChain` starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainBase {
+    /// The base of the module with this name, or of the game executable if it is `None`.
+    Module(Option<String>),
+    /// A fixed address.
+    Address(usize),
+}
+
+/// A step of a `PointerChain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainStep {
+    /// Add an offset to the address.
+    Offset(isize),
+    /// Read the pointer at the address, which has to be non-null.
+    Deref,
+}
+
+/// A path to game data through several pointers, like `[[base+3D5DF38]+8]+68` in Cheat Engine.
+///
+/// The chain is resolved again on every access by default, as the objects along it may be replaced at any
+/// time. `cached` keeps the address from the first time it is resolved instead.
+///
+/// ```ignore
+/// let chain: PointerChain = "[[eldenring.exe+3D5DF38]+8]+68".parse()?;
+/// let value: u32 = unsafe { chain.read()? };
+/// ```
+#[derive(Debug)]
+pub struct PointerChain {
+    base: ChainBase,
+    steps: Vec<ChainStep>,
+    cache: Option<AtomicUsize>,
+}
+
+impl PointerChain {
+    /// A chain starting at `base`, which ends there until steps are added.
+    pub fn new(base: ChainBase) -> Self {
+        Self {
+            base,
+            steps: Vec::new(),
+            cache: None,
+        }
+    }
+    /// A chain starting at the base of the game executable.
+    pub fn game() -> Self {
+        Self::new(ChainBase::Module(None))
+    }
+    /// Add `offset` to the address.
+    pub fn offset(mut self, offset: isize) -> Self {
+        self.steps.push(ChainStep::Offset(offset));
+        self
+    }
+    /// Follow the pointer at the address.
+    pub fn deref(mut self) -> Self {
+        self.steps.push(ChainStep::Deref);
+        self
+    }
+    /// Follow the pointer at the address plus each offset but the last, and add the last offset, like a
+    /// pointer in Cheat Engine. `offsets(&[0x3D5DF38, 0x8, 0x68])` is `[[base+3D5DF38]+8]+68`.
+    pub fn offsets(mut self, offsets: &[isize]) -> Self {
+        for (i, &offset) in offsets.iter().enumerate() {
+            if i != 0 {
+                self = self.deref();
+            }
+            self = self.offset(offset);
+        }
+        self
+    }
+    /// Keep the address the chain resolves to, instead of resolving it on every access, until `invalidate`
+    /// is called.
+    pub fn cached(mut self) -> Self {
+        self.cache = Some(AtomicUsize::new(0));
+        self
+    }
+    /// Resolve a cached chain again on the next access.
+    pub fn invalidate(&self) {
+        if let Some(cache) = &self.cache {
+            cache.store(0, Ordering::Relaxed);
+        }
+    }
+    pub fn base(&self) -> &ChainBase {
+        &self.base
+    }
+    pub fn steps(&self) -> &[ChainStep] {
+        &self.steps
+    }
+    /// Parse a chain in the notation of Cheat Engine, i.e. `[[base+3D5DF38]+8]+68`. Offsets are hexadecimal,
+    /// with or without `0x`. The base is `base` for the game executable, a module name like `eldenring.exe`,
+    /// or an address.
+    ///
+    /// Like in Cheat Engine, a base that is only hexadecimal digits is an address, so a module whose name is
+    /// one, like `cafe`, has to be named with its extension, i.e. `cafe.dll`.
+    pub fn parse(chain: &str) -> Result<Self, LiberError> {
+        let mut parser = Parser {
+            chain,
+            rest: chain.trim(),
+        };
+        let parsed = parser.chain()?;
+        if !parser.rest.is_empty() {
+            return Err(parser.error());
+        }
+        Ok(parsed)
+    }
+    /// The address the chain ends at, reading the pointers along it from `memory`.
+    pub fn resolve_in(&self, memory: &impl Memory) -> Result<usize, LiberError> {
+        if let Some(address) = self
+            .cache
+            .as_ref()
+            .map(|cache| cache.load(Ordering::Relaxed))
+        {
+            if address != 0 {
+                return Ok(address);
+            }
+        }
+        let mut address = match &self.base {
+            ChainBase::Module(name) => module_provider()
+                .module_base(name.as_deref())
+                .ok_or_else(|| LiberError::ModuleNotFound(name.clone()))?,
+            ChainBase::Address(address) => *address,
+        };
+        let mut hop = 0;
+        for step in &self.steps {
+            match *step {
+                ChainStep::Offset(offset) => address = address.wrapping_add_signed(offset),
+                ChainStep::Deref => {
+                    hop += 1;
+                    let mut pointer = [0; size_of::<usize>()];
+                    if !memory.read(address, &mut pointer) {
+                        return Err(LiberError::UnreadableMemory(address));
+                    }
+                    address = usize::from_le_bytes(pointer);
+                    if address == 0 {
+                        return Err(LiberError::NullPointerInChain(self.to_string(), hop));
+                    }
+                }
+            }
+        }
+        if let Some(cache) = &self.cache {
+            cache.store(address, Ordering::Relaxed);
+        }
+        Ok(address)
+    }
+    /// Read the `T` at the end of the chain from `memory`.
+    pub fn read_in<T: Plain>(&self, memory: &impl Memory) -> Result<T, LiberError> {
+        let address = self.resolve_in(memory)?;
+        let mut value = MaybeUninit::<T>::uninit();
+        let bytes = unsafe {
+            std::slice::from_raw_parts_mut(value.as_mut_ptr().cast::<u8>(), size_of::<T>())
+        };
+        if !memory.read(address, bytes) {
+            return Err(LiberError::UnreadableMemory(address));
+        }
+        Ok(unsafe { value.assume_init() })
+    }
+    /// Write `value` at the end of the chain to `memory`.
+    pub fn write_in<T: Plain>(&self, memory: &impl Memory, value: T) -> Result<(), LiberError> {
+        let address = self.resolve_in(memory)?;
+        let bytes = unsafe {
+            std::slice::from_raw_parts((&value as *const T).cast::<u8>(), size_of::<T>())
+        };
+        if !memory.write(address, bytes) {
+            return Err(LiberError::UnwritableMemory(address));
+        }
+        Ok(())
+    }
+    /// The address the chain ends at in the game.
+    ///
+    /// # Safety
+    /// Every pointer along the chain has to be null or valid. See `ProcessMemory::new`.
+    pub unsafe fn resolve(&self) -> Result<usize, LiberError> {
+        self.resolve_in(&ProcessMemory::new())
+    }
+    /// Read the `T` at the end of the chain in the game.
+    ///
+    /// # Safety
+    /// See `resolve`. The `T` at the end has to be readable as well.
+    pub unsafe fn read<T: Plain>(&self) -> Result<T, LiberError> {
+        self.read_in(&ProcessMemory::new())
+    }
+    /// Write `value` at the end of the chain in the game.
+    ///
+    /// # Safety
+    /// See `resolve`. The `T` at the end has to be writable as well.
+    pub unsafe fn write<T: Plain>(&self, value: T) -> Result<(), LiberError> {
+        self.write_in(&ProcessMemory::new(), value)
+    }
+}
+
+impl Clone for PointerChain {
+    /// Clones the chain, without the cached address.
+    fn clone(&self) -> Self {
+        Self {
+            base: self.base.clone(),
+            steps: self.steps.clone(),
+            cache: self.cache.as_ref().map(|_| AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl PartialEq for PointerChain {
+    fn eq(&self, other: &Self) -> bool {
+        self.base == other.base && self.steps == other.steps
+    }
+}
+
+impl FromStr for PointerChain {
+    type Err = LiberError;
+
+    fn from_str(chain: &str) -> Result<Self, Self::Err> {
+        Self::parse(chain)
+    }
+}
+
+impl Display for PointerChain {
+    /// Formats the chain in the notation of Cheat Engine.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let derefs = self
+            .steps
+            .iter()
+            .filter(|&&step| step == ChainStep::Deref)
+            .count();
+        write!(f, "{}", "[".repeat(derefs))?;
+        match &self.base {
+            ChainBase::Module(None) => write!(f, "base")?,
+            ChainBase::Module(Some(name)) => write!(f, "{name}")?,
+            ChainBase::Address(address) => write!(f, "{address:X}")?,
+        }
+        for step in &self.steps {
+            match *step {
+                ChainStep::Offset(offset) if offset < 0 => {
+                    write!(f, "-{:X}", offset.unsigned_abs())?
+                }
+                ChainStep::Offset(offset) => write!(f, "+{offset:X}")?,
+                ChainStep::Deref => write!(f, "]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Parser<'a> {
+    chain: &'a str,
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    fn error(&self) -> LiberError {
+        LiberError::InvalidPointerChain(self.chain.to_string())
+    }
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest.trim_start();
+                true
+            }
+            None => false,
+        }
+    }
+    fn token(&mut self) -> &str {
+        let len = self
+            .rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(self.rest.len());
+        let (token, rest) = self.rest.split_at(len);
+        self.rest = rest.trim_start();
+        token
+    }
+    fn chain(&mut self) -> Result<PointerChain, LiberError> {
+        let mut chain = if self.eat('[') {
+            let chain = self.chain()?;
+            if !self.eat(']') {
+                return Err(self.error());
+            }
+            chain.deref()
+        } else {
+            let token = self.token();
+            let base = if token.eq_ignore_ascii_case("base") {
+                ChainBase::Module(None)
+            } else if let Some(address) = parse_hex(token) {
+                ChainBase::Address(address)
+            } else if !token.is_empty() {
+                ChainBase::Module(Some(token.to_string()))
+            } else {
+                return Err(self.error());
+            };
+            PointerChain::new(base)
+        };
+        loop {
+            let negative = if self.eat('+') {
+                false
+            } else if self.eat('-') {
+                true
+            } else {
+                return Ok(chain);
+            };
+            let offset = parse_hex(self.token())
+                .and_then(|offset| isize::try_from(offset).ok())
+                .ok_or_else(|| self.error())?;
+            chain = chain.offset(if negative { -offset } else { offset });
+        }
+    }
+}
+
+fn parse_hex(token: &str) -> Option<usize> {
+    let digits = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+        .unwrap_or(token);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    usize::from_str_radix(digits, 16).ok()
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Reads and writes memory of the game, so code that follows pointers through it, like `PointerChain`, can be
+/// tested against a `FakeMemory` instead.
+pub trait Memory {
+    /// Read `buffer.len()` bytes at `address`. Returns `false` if the memory can not be read.
+    fn read(&self, address: usize, buffer: &mut [u8]) -> bool;
+    /// Write `bytes` at `address`. Returns `false` if the memory can not be written.
+    fn write(&self, address: usize, bytes: &[u8]) -> bool;
+}
+
+/// The memory of the process, accessed directly.
+#[derive(Debug)]
+pub struct ProcessMemory(());
+
+impl ProcessMemory {
+    /// # Safety
+    /// Every address read or written through it has to be null or valid for the access.
+    pub const unsafe fn new() -> Self {
+        Self(())
+    }
+}
+
+impl Memory for ProcessMemory {
+    fn read(&self, address: usize, buffer: &mut [u8]) -> bool {
+        if address == 0 {
+            return false;
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(address as *const u8, buffer.as_mut_ptr(), buffer.len())
+        };
+        true
+    }
+    fn write(&self, address: usize, bytes: &[u8]) -> bool {
+        if address == 0 {
+            return false;
+        }
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), address as *mut u8, bytes.len()) };
+        true
+    }
+}
+
+/// Memory made of regions at fixed addresses, for testing code that follows pointers without the game.
+#[derive(Debug, Default)]
+pub struct FakeMemory {
+    regions: Mutex<BTreeMap<usize, Vec<u8>>>,
+}
+
+impl FakeMemory {
+    /// Memory without any regions, where every access fails.
+    pub const fn new() -> Self {
+        Self {
+            regions: Mutex::new(BTreeMap::new()),
+        }
+    }
+    /// Add a region with `bytes` at `address`.
+    pub fn with_region(self, address: usize, bytes: impl Into<Vec<u8>>) -> Self {
+        self.insert(address, bytes);
+        self
+    }
+    /// Add a region with `bytes` at `address`, replacing the region there.
+    pub fn insert(&self, address: usize, bytes: impl Into<Vec<u8>>) {
+        self.regions().insert(address, bytes.into());
+    }
+    /// Call `f` with the bytes of the region `len` bytes at `address` are in, and the offset of `address` in
+    /// them.
+    fn access<R>(
+        &self,
+        address: usize,
+        len: usize,
+        f: impl FnOnce(&mut Vec<u8>, usize) -> R,
+    ) -> Option<R> {
+        let mut regions = self.regions();
+        let (&start, region) = regions.range_mut(..=address).next_back()?;
+        let offset = address - start;
+        (offset.checked_add(len)? <= region.len()).then(|| f(region, offset))
+    }
+    fn regions(&self) -> std::sync::MutexGuard<'_, BTreeMap<usize, Vec<u8>>> {
+        self.regions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Memory for FakeMemory {
+    fn read(&self, address: usize, buffer: &mut [u8]) -> bool {
+        self.access(address, buffer.len(), |region, offset| {
+            buffer.copy_from_slice(&region[offset..offset + buffer.len()])
+        })
+        .is_some()
+    }
+    fn write(&self, address: usize, bytes: &[u8]) -> bool {
+        self.access(address, bytes.len(), |region, offset| {
+            region[offset..offset + bytes.len()].copy_from_slice(bytes)
+        })
+        .is_some()
+    }
+}
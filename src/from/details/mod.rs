@@ -1 +1,12 @@
+pub(crate) mod cache;
+pub(crate) mod chain;
+pub(crate) mod memory;
+pub(crate) mod module;
+pub(crate) mod pattern;
+pub(crate) mod pe;
+pub(crate) mod rtti;
 pub(crate) mod symbols;
+mod tests;
+pub(crate) mod validate;
+pub(crate) mod version;
+pub(crate) mod x86;
@@ -0,0 +1,201 @@
+use std::sync::RwLock;
+
+/// Looks up the modules loaded into the process, like the game executable, so addresses in them can be
+/// resolved.
+///
+/// The provider used by the crate is set with `set_module_provider`. It defaults to `NativeModuleProvider`,
+/// which is `GetModuleHandle` on Windows and `/proc/self/maps` on Linux. Tests can inject a
+/// `MockModuleProvider` with a fake image base instead.
+pub trait ModuleProvider: Send + Sync {
+    /// Base address of the module with the file name `name`, i.e. `eldenring.exe`, or of the main executable
+    /// if `name` is `None`. Returns `None` if the module is not loaded.
+    fn module_base(&self, name: Option<&str>) -> Option<usize>;
+}
+
+#[cfg(windows)]
+pub type NativeModuleProvider = WindowsModuleProvider;
+#[cfg(target_os = "linux")]
+pub type NativeModuleProvider = ProcMapsModuleProvider;
+
+#[cfg(any(windows, target_os = "linux"))]
+static NATIVE_MODULE_PROVIDER: NativeModuleProvider = NativeModuleProvider::new();
+#[cfg(not(any(windows, target_os = "linux")))]
+static NATIVE_MODULE_PROVIDER: MockModuleProvider = MockModuleProvider::new();
+
+static MODULE_PROVIDER: RwLock<&'static dyn ModuleProvider> = RwLock::new(&NATIVE_MODULE_PROVIDER);
+
+/// The module provider used to resolve addresses.
+pub fn module_provider() -> &'static dyn ModuleProvider {
+    *MODULE_PROVIDER.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replace the module provider used to resolve addresses. The provider lives for the rest of the process.
+pub fn set_module_provider(provider: impl ModuleProvider + 'static) {
+    let provider: &'static dyn ModuleProvider = Box::leak(Box::new(provider));
+    *MODULE_PROVIDER.write().unwrap_or_else(|e| e.into_inner()) = provider;
+}
+
+/// The path of the module this crate is linked into, i.e. the DLL of the mod.
+pub fn current_module_path() -> Option<std::path::PathBuf> {
+    module_path_of(current_module_path as *const () as usize)
+}
+
+#[cfg(windows)]
+fn module_path_of(address: usize) -> Option<std::path::PathBuf> {
+    use std::os::windows::ffi::OsStringExt;
+    const FROM_ADDRESS: u32 = 0x4;
+    const UNCHANGED_REFCOUNT: u32 = 0x2;
+    let mut module = 0;
+    if unsafe {
+        GetModuleHandleExW(
+            FROM_ADDRESS | UNCHANGED_REFCOUNT,
+            address as *const u16,
+            &mut module,
+        )
+    } == 0
+    {
+        return None;
+    }
+    let mut path = vec![0u16; 1024];
+    let len = unsafe { GetModuleFileNameW(module, path.as_mut_ptr(), path.len() as u32) } as usize;
+    (len != 0 && len < path.len()).then(|| std::ffi::OsString::from_wide(&path[..len]).into())
+}
+
+#[cfg(target_os = "linux")]
+fn module_path_of(address: usize) -> Option<std::path::PathBuf> {
+    let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+    maps.lines().find_map(|line| {
+        let mut columns = line.splitn(6, ' ');
+        let (start, end) = columns.next()?.split_once('-')?;
+        let path = columns.nth(4)?.trim_start();
+        let range = usize::from_str_radix(start, 16).ok()?..usize::from_str_radix(end, 16).ok()?;
+        (range.contains(&address) && path.starts_with('/')).then(|| path.into())
+    })
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn module_path_of(_address: usize) -> Option<std::path::PathBuf> {
+    None
+}
+
+/// Looks up modules with `GetModuleHandleA`.
+#[cfg(windows)]
+#[derive(Debug, Default)]
+pub struct WindowsModuleProvider;
+
+#[cfg(windows)]
+#[link(name = "kernel32", kind = "raw-dylib")]
+extern "system" {
+    fn GetModuleHandleA(name: *const u8) -> usize;
+    fn GetModuleHandleExW(flags: u32, name: *const u16, module: *mut usize) -> i32;
+    fn GetModuleFileNameW(module: usize, path: *mut u16, size: u32) -> u32;
+}
+
+#[cfg(windows)]
+impl WindowsModuleProvider {
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+#[cfg(windows)]
+impl ModuleProvider for WindowsModuleProvider {
+    fn module_base(&self, name: Option<&str>) -> Option<usize> {
+        let base = match name {
+            Some(name) => {
+                let name = std::ffi::CString::new(name).ok()?;
+                unsafe { GetModuleHandleA(name.as_ptr() as *const u8) }
+            }
+            None => unsafe { GetModuleHandleA(std::ptr::null::<u8>()) },
+        };
+        (base != 0).then_some(base)
+    }
+}
+
+/// Looks up modules in the memory mappings of the process in `/proc/self/maps`, the way `dlopen`ed libraries
+/// and the executable itself are mapped on Linux.
+///
+/// The base of a module is the start of its lowest mapping. The mappings are read on every lookup, as modules
+/// may be loaded and unloaded at any time.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Default)]
+pub struct ProcMapsModuleProvider;
+
+#[cfg(target_os = "linux")]
+impl ProcMapsModuleProvider {
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl ModuleProvider for ProcMapsModuleProvider {
+    fn module_base(&self, name: Option<&str>) -> Option<usize> {
+        let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+        let exe = std::fs::read_link("/proc/self/exe").ok();
+        maps_module_base(&maps, |path| match name {
+            Some(name) => path.rsplit('/').next() == Some(name),
+            None => exe.as_deref() == Some(std::path::Path::new(path)),
+        })
+    }
+}
+
+/// Find the lowest start address of the mappings in `maps`, in the format of `/proc/self/maps`, whose path
+/// matches.
+#[cfg(any(target_os = "linux", test))]
+pub(crate) fn maps_module_base(maps: &str, matches: impl Fn(&str) -> bool) -> Option<usize> {
+    maps.lines()
+        .filter_map(|line| {
+            // address perms offset dev inode path
+            let mut columns = line.splitn(6, ' ');
+            let range = columns.next()?;
+            let path = columns.nth(4)?.trim_start();
+            let start = range.split('-').next()?;
+            matches(path)
+                .then(|| usize::from_str_radix(start, 16).ok())
+                .flatten()
+        })
+        .min()
+}
+
+/// A module provider with fixed module bases, for testing code that resolves addresses without the game.
+#[derive(Debug, Default)]
+pub struct MockModuleProvider {
+    base: Option<usize>,
+    modules: Vec<(String, usize)>,
+}
+
+impl MockModuleProvider {
+    /// A provider without any modules.
+    pub const fn new() -> Self {
+        Self {
+            base: None,
+            modules: Vec::new(),
+        }
+    }
+    /// A provider with a main executable at `base`, i.e. the usual image base `0x140000000`.
+    pub const fn with_base(base: usize) -> Self {
+        Self {
+            base: Some(base),
+            modules: Vec::new(),
+        }
+    }
+    /// Add a module named `name` at `base`.
+    pub fn with_module(mut self, name: impl Into<String>, base: usize) -> Self {
+        self.modules.push((name.into(), base));
+        self
+    }
+}
+
+impl ModuleProvider for MockModuleProvider {
+    fn module_base(&self, name: Option<&str>) -> Option<usize> {
+        match name {
+            Some(name) => self
+                .modules
+                .iter()
+                .find(|(module, _)| module.eq_ignore_ascii_case(name))
+                .map(|&(_, base)| base),
+            None => self.base,
+        }
+    }
+}
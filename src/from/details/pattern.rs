@@ -0,0 +1,216 @@
+use std::fmt::{Display, Formatter};
+use std::thread;
+
+use crate::from::details::cache::cached_game_rva;
+use crate::from::details::pe::PeImage;
+use crate::LiberError;
+
+/// Haystacks at least this long are scanned on several threads by `Pattern::find_all`.
+const PARALLEL_THRESHOLD: usize = 4 << 20;
+
+/// A byte pattern with wildcards, to find code and data that moves between game versions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    bytes: Vec<u8>,
+    mask: Vec<bool>,
+}
+
+impl Pattern {
+    /// Parse an IDA-style signature, with bytes in hex separated by spaces and `?` or `??` as wildcards, i.e.
+    /// `48 8B ?? ?? E8`.
+    pub fn new(signature: &str) -> Result<Self, LiberError> {
+        let mut bytes = Vec::new();
+        let mut mask = Vec::new();
+        for token in signature.split_whitespace() {
+            if token == "?" || token == "??" {
+                bytes.push(0);
+                mask.push(false);
+            } else if token.len() == 2 {
+                let byte = u8::from_str_radix(token, 16)
+                    .map_err(|_| LiberError::InvalidPattern(signature.to_string()))?;
+                bytes.push(byte);
+                mask.push(true);
+            } else {
+                return Err(LiberError::InvalidPattern(signature.to_string()));
+            }
+        }
+        Self::from_parts(bytes, mask, signature)
+    }
+    /// A pattern from `bytes` and a code-style `mask`, with `x` for bytes that have to match and `?` for
+    /// wildcards, i.e. `b"\x48\x8B\x00\x00\xE8"` and `xx??x`.
+    pub fn from_mask(bytes: &[u8], mask: &str) -> Result<Self, LiberError> {
+        let invalid = || LiberError::InvalidPattern(mask.to_string());
+        if bytes.len() != mask.len() {
+            return Err(invalid());
+        }
+        let flags = mask
+            .chars()
+            .map(|c| match c {
+                'x' => Ok(true),
+                '?' => Ok(false),
+                _ => Err(invalid()),
+            })
+            .collect::<Result<_, _>>()?;
+        Self::from_parts(bytes.to_vec(), flags, mask)
+    }
+    fn from_parts(bytes: Vec<u8>, mask: Vec<bool>, source: &str) -> Result<Self, LiberError> {
+        if !mask.contains(&true) {
+            return Err(LiberError::InvalidPattern(source.to_string()));
+        }
+        Ok(Self { bytes, mask })
+    }
+    /// The length of the pattern in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+    /// Returns `true` if the pattern is empty, which a parsed pattern never is.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+    /// Returns `true` if the pattern matches `haystack` at `offset`.
+    pub fn matches_at(&self, haystack: &[u8], offset: usize) -> bool {
+        haystack
+            .get(offset..offset + self.len())
+            .is_some_and(|window| {
+                window
+                    .iter()
+                    .zip(&self.bytes)
+                    .zip(&self.mask)
+                    .all(|((byte, expected), &mask)| !mask || byte == expected)
+            })
+    }
+    /// The first byte that has to match, and its index in the pattern, to find candidates quickly.
+    fn anchor(&self) -> (usize, u8) {
+        let index = self.mask.iter().position(|&mask| mask).unwrap_or(0);
+        (index, self.bytes[index])
+    }
+    /// Call `found` with the offset of every match starting in `starts`.
+    fn scan(
+        &self,
+        haystack: &[u8],
+        starts: std::ops::Range<usize>,
+        mut found: impl FnMut(usize) -> bool,
+    ) {
+        let (index, anchor) = self.anchor();
+        let mut start = starts.start;
+        while start < starts.end {
+            let candidates = &haystack[start + index..starts.end + index];
+            let Some(position) = candidates.iter().position(|&byte| byte == anchor) else {
+                return;
+            };
+            start += position;
+            if self.matches_at(haystack, start) && !found(start) {
+                return;
+            }
+            start += 1;
+        }
+    }
+    /// The number of offsets a match can start at in `haystack`.
+    fn starts(&self, haystack: &[u8]) -> usize {
+        (haystack.len() + 1).saturating_sub(self.len())
+    }
+    /// The offset of the first match in `haystack`.
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        let mut first = None;
+        self.scan(haystack, 0..self.starts(haystack), |offset| {
+            first = Some(offset);
+            false
+        });
+        first
+    }
+    /// The offsets of all matches in `haystack`, in order. Large haystacks, like the `.text` section of the
+    /// game, are scanned on all available threads.
+    pub fn find_all(&self, haystack: &[u8]) -> Vec<usize> {
+        let threads = if haystack.len() >= PARALLEL_THRESHOLD {
+            thread::available_parallelism().map_or(1, |threads| threads.get())
+        } else {
+            1
+        };
+        self.find_all_with_threads(haystack, threads)
+    }
+    /// Like `find_all`, scanning `haystack` in `threads` chunks in parallel.
+    pub fn find_all_with_threads(&self, haystack: &[u8], threads: usize) -> Vec<usize> {
+        let starts = self.starts(haystack);
+        let chunk = starts.div_ceil(threads.max(1)).max(1);
+        if chunk >= starts {
+            let mut matches = Vec::new();
+            self.scan(haystack, 0..starts, |offset| {
+                matches.push(offset);
+                true
+            });
+            return matches;
+        }
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..starts)
+                .step_by(chunk)
+                .map(|start| {
+                    scope.spawn(move || {
+                        let mut matches = Vec::new();
+                        self.scan(haystack, start..(start + chunk).min(starts), |offset| {
+                            matches.push(offset);
+                            true
+                        });
+                        matches
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect()
+        })
+    }
+    /// The offset of the only match in `haystack`. Fails if there is none, or more than one, as then the
+    /// pattern most likely does not find what it was written for.
+    pub fn find_unique(&self, haystack: &[u8]) -> Result<usize, LiberError> {
+        match self.find_all(haystack)[..] {
+            [offset] => Ok(offset),
+            [] => Err(LiberError::PatternNotFound(self.to_string())),
+            ref matches => Err(LiberError::PatternNotUnique(
+                self.to_string(),
+                matches.len(),
+            )),
+        }
+    }
+    /// The RVA of the only match in the section named `section` of `image`.
+    pub fn find_in_section(&self, image: &PeImage, section: &str) -> Result<usize, LiberError> {
+        let section = image.section(section)?;
+        let offset = self.find_unique(image.section_data(&section))?;
+        Ok(section.virtual_address as usize + offset)
+    }
+    /// The address of the only match in the section named `section` of the game executable, i.e. `.text`.
+    ///
+    /// The address is saved in the address cache, and only searched for again if the pattern does not match
+    /// at the cached address anymore.
+    pub fn find_in_game(&self, section: &str) -> Result<usize, LiberError> {
+        let image = PeImage::game()?;
+        let rva = cached_game_rva(
+            &image,
+            &format!("pattern {section} {self}"),
+            |rva| self.matches_at(image.data(), rva as usize),
+            || Ok(self.find_in_section(&image, section)? as u32),
+        )?;
+        Ok(image.data().as_ptr() as usize + rva as usize)
+    }
+}
+
+impl Display for Pattern {
+    /// Formats the pattern as an IDA-style signature.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, (byte, &mask)) in self.bytes.iter().zip(&self.mask).enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            if mask {
+                write!(f, "{byte:02X}")?;
+            } else {
+                write!(f, "??")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,550 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{get_base_address, LiberError};
+
+/// The layout of a PE image in a byte slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageLayout {
+    /// The image as the loader mapped it, with the sections at their RVAs, like the modules of the process.
+    Mapped,
+    /// The image as it is stored in the file, with the sections at their raw offsets.
+    File,
+}
+
+/// A section in the section table of a PE image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Section {
+    name: [u8; 8],
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub raw_offset: u32,
+    pub raw_size: u32,
+    pub characteristics: u32,
+}
+
+impl Section {
+    /// The name of the section, i.e. `.text`.
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(8);
+        std::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+    /// The RVA `offset` bytes into the section, which can overflow in a corrupt image.
+    pub fn rva_of(&self, offset: usize) -> Result<u32, LiberError> {
+        u32::try_from(offset)
+            .ok()
+            .and_then(|offset| self.virtual_address.checked_add(offset))
+            .ok_or_else(|| {
+                LiberError::InvalidImage(format!(
+                    "offset {offset:#x} of section {} overflows",
+                    self.name()
+                ))
+            })
+    }
+}
+
+/// An entry of the data directories of a PE image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataDirectory {
+    pub virtual_address: u32,
+    pub size: u32,
+}
+
+impl DataDirectory {
+    pub const EXPORT: usize = 0;
+    pub const IMPORT: usize = 1;
+    pub const RESOURCE: usize = 2;
+    pub const EXCEPTION: usize = 3;
+
+    /// Returns `true` if `rva` is inside the directory.
+    pub fn contains(&self, rva: u32) -> bool {
+        rva >= self.virtual_address && rva - self.virtual_address < self.size
+    }
+}
+
+/// A function exported by a PE image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    /// The name of the export, unless it is only exported by ordinal.
+    pub name: Option<String>,
+    pub ordinal: u32,
+    /// The RVA of the function. It is `0` for forwarded exports.
+    pub rva: u32,
+    /// The function this export is forwarded to, i.e. `NTDLL.RtlAllocateHeap`.
+    pub forwarder: Option<String>,
+}
+
+/// How a function is imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportSymbol {
+    Name(String),
+    Ordinal(u16),
+}
+
+/// A function imported by a PE image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// The file name of the module the function is imported from, i.e. `KERNEL32.dll`.
+    pub module: String,
+    pub symbol: ImportSymbol,
+    /// The RVA of the entry of the function in the import address table, which the loader fills with its
+    /// address.
+    pub address_rva: u32,
+}
+
+/// An entry of the exception directory (`.pdata`), with the unwind info of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeFunction {
+    pub begin: u32,
+    pub end: u32,
+    pub unwind_info: u32,
+}
+
+/// The file version in the version resource of an image, i.e. `2.6.0.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub build: u16,
+    pub revision: u16,
+}
+
+impl Display for FileVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.build, self.revision
+        )
+    }
+}
+
+/// A PE32+ image, like the game executable, in a byte slice.
+///
+/// It works on the module loaded into the process, with `PeImage::from_base`, and on a file read from disk:
+///
+/// ```no_run
+/// # use liber_rs::{ImageLayout, PeImage};
+/// let file = std::fs::read("eldenring.exe")?;
+/// let image = PeImage::new(&file, ImageLayout::File)?;
+/// println!("{:#x}", image.timestamp());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct PeImage<'a> {
+    data: &'a [u8],
+    layout: ImageLayout,
+    nt_headers: usize,
+}
+
+const SECTION_HEADER_SIZE: usize = 40;
+
+impl<'a> PeImage<'a> {
+    /// Parse the headers of the image in `data`.
+    pub fn new(data: &'a [u8], layout: ImageLayout) -> Result<Self, LiberError> {
+        if data.get(..2) != Some(b"MZ") {
+            return Err(LiberError::InvalidImage("missing DOS signature".into()));
+        }
+        let nt_headers = read_u32(data, 0x3C)? as usize;
+        if data.get(nt_headers..nt_headers + 4) != Some(b"PE\0\0") {
+            return Err(LiberError::InvalidImage("missing NT signature".into()));
+        }
+        let image = Self {
+            data,
+            layout,
+            nt_headers,
+        };
+        if read_u16(data, image.optional_header())? != 0x20B {
+            return Err(LiberError::InvalidImage("not a PE32+ image".into()));
+        }
+        Ok(image)
+    }
+    /// The image of the module loaded at `base`.
+    ///
+    /// # Safety
+    /// `base` must be the base address of a module that stays loaded for `'static`, i.e. the game executable.
+    pub unsafe fn from_base(base: usize) -> Result<PeImage<'static>, LiberError> {
+        let headers = std::slice::from_raw_parts(base as *const u8, 0x40);
+        if &headers[..2] != b"MZ" {
+            return Err(LiberError::InvalidImage("missing DOS signature".into()));
+        }
+        let nt_headers = read_u32(headers, 0x3C)? as usize;
+        if nt_headers > 0x1000 {
+            return Err(LiberError::InvalidImage(
+                "NT headers are out of bounds".into(),
+            ));
+        }
+        let headers = std::slice::from_raw_parts(base as *const u8, nt_headers + 0x58);
+        if &headers[nt_headers..nt_headers + 4] != b"PE\0\0" {
+            return Err(LiberError::InvalidImage("missing NT signature".into()));
+        }
+        let size = read_u32(headers, nt_headers + 0x18 + 0x38)? as usize;
+        PeImage::new(
+            std::slice::from_raw_parts(base as *const u8, size),
+            ImageLayout::Mapped,
+        )
+    }
+    /// The image of the game executable.
+    pub fn game() -> Result<PeImage<'static>, LiberError> {
+        unsafe { PeImage::from_base(get_base_address()?) }
+    }
+    /// The bytes of the image.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+    /// The layout of the bytes of the image.
+    pub fn layout(&self) -> ImageLayout {
+        self.layout
+    }
+    fn optional_header(&self) -> usize {
+        self.nt_headers + 0x18
+    }
+    /// The target machine, `0x8664` for x86-64.
+    pub fn machine(&self) -> u16 {
+        read_u16(self.data, self.nt_headers + 0x4).unwrap_or(0)
+    }
+    /// The time the image was linked, in seconds since the Unix epoch. It identifies the build of the game.
+    pub fn timestamp(&self) -> u32 {
+        read_u32(self.data, self.nt_headers + 0x8).unwrap_or(0)
+    }
+    /// The RVA of the entry point.
+    pub fn entry_point(&self) -> u32 {
+        read_u32(self.data, self.optional_header() + 0x10).unwrap_or(0)
+    }
+    /// The preferred base address of the image.
+    pub fn image_base(&self) -> u64 {
+        read_u64(self.data, self.optional_header() + 0x18).unwrap_or(0)
+    }
+    /// The address the image is loaded at, which absolute pointers in it are relative to. It is the preferred
+    /// base for images read from a file, as relocations have not been applied to them.
+    pub fn load_address(&self) -> u64 {
+        match self.layout {
+            ImageLayout::Mapped => self.data.as_ptr() as u64,
+            ImageLayout::File => self.image_base(),
+        }
+    }
+    /// The size of the image when it is mapped.
+    pub fn size_of_image(&self) -> u32 {
+        read_u32(self.data, self.optional_header() + 0x38).unwrap_or(0)
+    }
+    /// The size of the headers, which are mapped at the start of the image.
+    pub fn size_of_headers(&self) -> u32 {
+        read_u32(self.data, self.optional_header() + 0x3C).unwrap_or(0)
+    }
+    /// The data directory at `index`, i.e. `DataDirectory::IMPORT`, if the image has it.
+    pub fn data_directory(&self, index: usize) -> Option<DataDirectory> {
+        let count = read_u32(self.data, self.optional_header() + 0x6C).ok()? as usize;
+        if index >= count {
+            return None;
+        }
+        let entry = self.optional_header() + 0x70 + index.checked_mul(8)?;
+        let directory = DataDirectory {
+            virtual_address: read_u32(self.data, entry).ok()?,
+            size: read_u32(self.data, entry + 4).ok()?,
+        };
+        (directory.virtual_address != 0).then_some(directory)
+    }
+    /// The sections of the image.
+    pub fn sections(&self) -> impl Iterator<Item = Section> + 'a {
+        let data = self.data;
+        let count = read_u16(data, self.nt_headers + 0x6).unwrap_or(0) as usize;
+        let optional_size = read_u16(data, self.nt_headers + 0x14).unwrap_or(0) as usize;
+        let table = self.optional_header() + optional_size;
+        (0..count).map_while(move |i| {
+            let header = table + i * SECTION_HEADER_SIZE;
+            Some(Section {
+                name: data.get(header..header + 8)?.try_into().ok()?,
+                virtual_size: read_u32(data, header + 0x8).ok()?,
+                virtual_address: read_u32(data, header + 0xC).ok()?,
+                raw_size: read_u32(data, header + 0x10).ok()?,
+                raw_offset: read_u32(data, header + 0x14).ok()?,
+                characteristics: read_u32(data, header + 0x24).ok()?,
+            })
+        })
+    }
+    /// The section named `name`.
+    pub fn section(&self, name: &str) -> Result<Section, LiberError> {
+        self.sections()
+            .find(|section| section.name() == name)
+            .ok_or_else(|| LiberError::InvalidImage(format!("missing section {name}")))
+    }
+    /// The offset of `rva` in the bytes of the image.
+    pub fn rva_to_offset(&self, rva: u32) -> Option<usize> {
+        match self.layout {
+            ImageLayout::Mapped => Some(rva as usize),
+            ImageLayout::File if rva < self.size_of_headers() => Some(rva as usize),
+            ImageLayout::File => self
+                .sections()
+                .find(|section| {
+                    rva >= section.virtual_address
+                        && rva - section.virtual_address < section.raw_size
+                })
+                .and_then(|section| {
+                    let offset = section
+                        .raw_offset
+                        .checked_add(rva - section.virtual_address)?;
+                    Some(offset as usize)
+                }),
+        }
+    }
+    /// The `len` bytes at `rva`.
+    pub fn read(&self, rva: u32, len: usize) -> Option<&'a [u8]> {
+        let offset = self.rva_to_offset(rva)?;
+        self.data.get(offset..offset.checked_add(len)?)
+    }
+    fn read_u16(&self, rva: u32) -> Result<u16, LiberError> {
+        read_u16(self.read(rva, 2).ok_or_else(|| out_of_bounds(rva))?, 0)
+    }
+    fn read_u32(&self, rva: u32) -> Result<u32, LiberError> {
+        read_u32(self.read(rva, 4).ok_or_else(|| out_of_bounds(rva))?, 0)
+    }
+    fn read_u64(&self, rva: u32) -> Result<u64, LiberError> {
+        read_u64(self.read(rva, 8).ok_or_else(|| out_of_bounds(rva))?, 0)
+    }
+    /// The null-terminated string at `rva`.
+    pub fn read_c_str(&self, rva: u32) -> Result<String, LiberError> {
+        let offset = self.rva_to_offset(rva).ok_or_else(|| out_of_bounds(rva))?;
+        let bytes = self.data.get(offset..).unwrap_or_default();
+        let len = bytes
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| out_of_bounds(rva))?;
+        Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+    /// The functions exported by the image.
+    pub fn exports(&self) -> Result<Vec<Export>, LiberError> {
+        let Some(directory) = self.data_directory(DataDirectory::EXPORT) else {
+            return Ok(Vec::new());
+        };
+        let table = directory.virtual_address;
+        let ordinal_base = self.read_u32(rva_add(table, 0x10)?)?;
+        let function_count = self.read_u32(rva_add(table, 0x14)?)?;
+        let name_count = self.read_u32(rva_add(table, 0x18)?)?;
+        let functions = self.read_u32(rva_add(table, 0x1C)?)?;
+        let names = self.read_u32(rva_add(table, 0x20)?)?;
+        let name_ordinals = self.read_u32(rva_add(table, 0x24)?)?;
+        let mut exports = (0..function_count)
+            .map(|i| {
+                let rva = self.read_u32(rva_index(functions, i, 4)?)?;
+                let forwarder = directory
+                    .contains(rva)
+                    .then(|| self.read_c_str(rva))
+                    .transpose()?;
+                Ok(Export {
+                    name: None,
+                    ordinal: ordinal_base.checked_add(i).ok_or_else(|| {
+                        LiberError::InvalidImage("export ordinals overflow".into())
+                    })?,
+                    rva: if forwarder.is_some() { 0 } else { rva },
+                    forwarder,
+                })
+            })
+            .collect::<Result<Vec<_>, LiberError>>()?;
+        for i in 0..name_count {
+            let index = self.read_u16(rva_index(name_ordinals, i, 2)?)? as usize;
+            let name = self.read_c_str(self.read_u32(rva_index(names, i, 4)?)?)?;
+            let export = exports.get_mut(index).ok_or_else(|| {
+                LiberError::InvalidImage(format!("export {name} is out of bounds"))
+            })?;
+            export.name = Some(name);
+        }
+        Ok(exports)
+    }
+    /// The RVA of the function exported as `name`.
+    pub fn export(&self, name: &str) -> Result<u32, LiberError> {
+        self.exports()?
+            .into_iter()
+            .find(|export| export.name.as_deref() == Some(name) && export.forwarder.is_none())
+            .map(|export| export.rva)
+            .ok_or_else(|| LiberError::MissingSymbol(name.to_string()))
+    }
+    /// The functions imported by the image.
+    pub fn imports(&self) -> Result<Vec<Import>, LiberError> {
+        let Some(directory) = self.data_directory(DataDirectory::IMPORT) else {
+            return Ok(Vec::new());
+        };
+        let mut imports = Vec::new();
+        for index in 0.. {
+            let descriptor = rva_index(directory.virtual_address, index, 20)?;
+            let lookup = self.read_u32(descriptor)?;
+            let name = self.read_u32(rva_add(descriptor, 0xC)?)?;
+            let addresses = self.read_u32(rva_add(descriptor, 0x10)?)?;
+            if name == 0 && addresses == 0 {
+                break;
+            }
+            let module = self.read_c_str(name)?;
+            // The lookup table is optional, and the address table is a copy of it until the image is loaded.
+            let lookup = if lookup != 0 { lookup } else { addresses };
+            for i in 0.. {
+                let thunk = self.read_u64(rva_index(lookup, i, 8)?)?;
+                if thunk == 0 {
+                    break;
+                }
+                let symbol = if thunk & (1 << 63) != 0 {
+                    ImportSymbol::Ordinal(thunk as u16)
+                } else {
+                    ImportSymbol::Name(self.read_c_str(rva_add(thunk as u32, 2)?)?)
+                };
+                imports.push(Import {
+                    module: module.clone(),
+                    symbol,
+                    address_rva: rva_index(addresses, i, 8)?,
+                });
+            }
+        }
+        Ok(imports)
+    }
+    /// The entries of the exception directory, sorted by address.
+    pub fn runtime_functions(&self) -> Result<Vec<RuntimeFunction>, LiberError> {
+        let Some(directory) = self.data_directory(DataDirectory::EXCEPTION) else {
+            return Ok(Vec::new());
+        };
+        (0..directory.size / 12)
+            .map(|i| {
+                let entry = rva_index(directory.virtual_address, i, 12)?;
+                Ok(RuntimeFunction {
+                    begin: self.read_u32(entry)?,
+                    end: self.read_u32(rva_add(entry, 4)?)?,
+                    unwind_info: self.read_u32(rva_add(entry, 8)?)?,
+                })
+            })
+            .collect()
+    }
+    /// The entry of the exception directory of the function containing `rva`, which gives the bounds of the
+    /// function. Leaf functions have no entry.
+    pub fn function_containing(&self, rva: u32) -> Result<Option<RuntimeFunction>, LiberError> {
+        let functions = self.runtime_functions()?;
+        let index = functions.partition_point(|function| function.end <= rva);
+        Ok(functions
+            .get(index)
+            .filter(|function| function.begin <= rva)
+            .copied())
+    }
+    /// The data of the first resource of the type `kind`, i.e. `RT_VERSION`, in any language.
+    pub fn resource(&self, kind: u16) -> Result<Option<&'a [u8]>, LiberError> {
+        let Some(directory) = self.data_directory(DataDirectory::RESOURCE) else {
+            return Ok(None);
+        };
+        let root = directory.virtual_address;
+        // The directory has three levels, by type, by name and by language. Types are IDs, which follow the
+        // named entries.
+        let mut entry = 0;
+        let mut table = root;
+        for level in 0..3 {
+            let named = self.read_u16(rva_add(table, 0xC)?)? as u32;
+            let ids = self.read_u16(rva_add(table, 0xE)?)? as u32;
+            let first = rva_add(table, 0x10)?;
+            let mut entries = (0..named + ids).map(|i| rva_index(first, i, 8));
+            let found = if level == 0 {
+                entries
+                    .skip(named as usize)
+                    .map(|entry| {
+                        let entry = entry?;
+                        Ok((entry, self.read_u32(entry)?))
+                    })
+                    .find(|found| !matches!(found, Ok((_, id)) if *id != kind as u32))
+                    .transpose()?
+                    .map(|(entry, _)| entry)
+            } else {
+                entries.next().transpose()?
+            };
+            let Some(found) = found else {
+                return Ok(None);
+            };
+            entry = self.read_u32(rva_add(found, 4)?)?;
+            table = rva_add(root, entry & 0x7FFF_FFFF)?;
+        }
+        if entry & 0x8000_0000 != 0 {
+            return Err(LiberError::InvalidImage(
+                "resource directory is too deep".into(),
+            ));
+        }
+        let data_entry = rva_add(root, entry)?;
+        let rva = self.read_u32(data_entry)?;
+        let size = self.read_u32(rva_add(data_entry, 4)?)? as usize;
+        Ok(Some(
+            self.read(rva, size).ok_or_else(|| out_of_bounds(rva))?,
+        ))
+    }
+    /// The file version in the `VS_VERSIONINFO` resource, if the image has one.
+    pub fn file_version(&self) -> Result<Option<FileVersion>, LiberError> {
+        const RT_VERSION: u16 = 16;
+        const SIGNATURE: [u8; 4] = 0xFEEF04BDu32.to_le_bytes();
+        let Some(info) = self.resource(RT_VERSION)? else {
+            return Ok(None);
+        };
+        // `VS_FIXEDFILEINFO` follows the key `VS_VERSION_INFO`, aligned to 4 bytes.
+        let Some(fixed) = (0..info.len().saturating_sub(16))
+            .step_by(4)
+            .find(|&offset| info[offset..offset + 4] == SIGNATURE)
+        else {
+            return Ok(None);
+        };
+        let most = read_u32(info, fixed + 8)?;
+        let least = read_u32(info, fixed + 12)?;
+        Ok(Some(FileVersion {
+            major: (most >> 16) as u16,
+            minor: most as u16,
+            build: (least >> 16) as u16,
+            revision: least as u16,
+        }))
+    }
+    /// The bytes of `section`. Offsets in them are relative to `section.virtual_address`.
+    pub fn section_data(&self, section: &Section) -> &'a [u8] {
+        let (start, size) = match self.layout {
+            ImageLayout::Mapped => (section.virtual_address, section.virtual_size),
+            ImageLayout::File => (
+                section.raw_offset,
+                section.raw_size.min(section.virtual_size),
+            ),
+        };
+        let start = (start as usize).min(self.data.len());
+        let end = start.saturating_add(size as usize).min(self.data.len());
+        &self.data[start..end]
+    }
+}
+
+/// `rva + offset`, for offsets read from the image, which can overflow in a corrupt one.
+fn rva_add(rva: u32, offset: u32) -> Result<u32, LiberError> {
+    rva.checked_add(offset)
+        .ok_or_else(|| LiberError::InvalidImage(format!("RVA {rva:#x} + {offset:#x} overflows")))
+}
+
+/// The RVA of the entry `index` of a table of `size` byte entries at `table`.
+fn rva_index(table: u32, index: u32, size: u32) -> Result<u32, LiberError> {
+    index
+        .checked_mul(size)
+        .and_then(|offset| table.checked_add(offset))
+        .ok_or_else(|| {
+            LiberError::InvalidImage(format!(
+                "entry {index} of the table at {table:#x} overflows"
+            ))
+        })
+}
+
+fn out_of_bounds(rva: u32) -> LiberError {
+    LiberError::InvalidImage(format!("RVA {rva:#x} is out of bounds"))
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Result<u16, LiberError> {
+    offset
+        .checked_add(2)
+        .and_then(|end| data.get(offset..end))
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| LiberError::InvalidImage(format!("{offset:#x} is out of bounds")))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Result<u32, LiberError> {
+    offset
+        .checked_add(4)
+        .and_then(|end| data.get(offset..end))
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| LiberError::InvalidImage(format!("{offset:#x} is out of bounds")))
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Result<u64, LiberError> {
+    offset
+        .checked_add(8)
+        .and_then(|end| data.get(offset..end))
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| LiberError::InvalidImage(format!("{offset:#x} is out of bounds")))
+}
@@ -0,0 +1,98 @@
+use crate::from::details::cache::cached_game_rva;
+use crate::from::details::pe::{read_u32, read_u64, PeImage};
+use crate::{LiberError, Pattern};
+
+/// The signature of a `CompleteObjectLocator` in an x64 image, whose references are RVAs.
+const COL_SIGNATURE: u32 = 1;
+
+/// The names the MSVC type descriptor of `class` can have, i.e. `.?AVCSEzTask@CS@@` for `CS::CSEzTask`. Names
+/// that are already mangled are kept as they are.
+fn type_descriptor_names(class: &str) -> Vec<String> {
+    if class.starts_with(".?A") {
+        return vec![class.to_string()];
+    }
+    let name: String = class.rsplit("::").map(|part| format!("{part}@")).collect();
+    // Classes and structs differ in the mangled name only.
+    vec![format!(".?AV{name}@"), format!(".?AU{name}@")]
+}
+
+/// The RVA of the vtable of `class` in `image`, found through the RTTI of the class. `class` is either a
+/// qualified name like `CS::CSEzTask`, or a mangled name like `.?AVCSEzTask@CS@@`.
+///
+/// The vtable is the one of the class itself, not of the subobjects of its secondary bases.
+pub fn find_vtable_rva(image: &PeImage, class: &str) -> Result<u32, LiberError> {
+    let missing = || LiberError::MissingSymbol(class.to_string());
+    // The name is at offset 0x10 of the type descriptor, after the pointers to the `type_info` vtable and to
+    // the demangled name.
+    let mut descriptors = Vec::new();
+    for name in type_descriptor_names(class) {
+        let mut bytes = name.into_bytes();
+        bytes.push(0);
+        let pattern = Pattern::from_mask(&bytes, &"x".repeat(bytes.len()))?;
+        for section in image.sections() {
+            for offset in pattern.find_all(image.section_data(&section)) {
+                descriptors.extend(section.rva_of(offset)?.checked_sub(0x10));
+            }
+        }
+    }
+    let rdata = image.section(".rdata")?;
+    let data = image.section_data(&rdata);
+    let locator = (0..data.len().saturating_sub(0x18))
+        .step_by(4)
+        .find(|&offset| {
+            let field = |at: usize| read_u32(data, offset + at).unwrap_or(0);
+            field(0x0) == COL_SIGNATURE
+                && field(0x4) == 0
+                && rdata.rva_of(offset) == Ok(field(0x14))
+                && descriptors.contains(&field(0xC))
+        })
+        .ok_or_else(missing)?;
+    // The pointer to the locator is right before the vtable.
+    let locator = image.load_address() + (rdata.virtual_address as usize + locator) as u64;
+    let pointer = (0..data.len().saturating_sub(8))
+        .step_by(8)
+        .find(|&offset| read_u64(data, offset) == Ok(locator))
+        .ok_or_else(missing)?;
+    rdata.rva_of(pointer + 8)
+}
+
+/// Returns `true` if the vtable at `rva` in `image` is the one of `class`, by following its RTTI back to the
+/// name of the class. This is much cheaper than `find_vtable_rva`.
+pub fn is_vtable_of(image: &PeImage, rva: u32, class: &str) -> bool {
+    let is_vtable_of = || -> Option<bool> {
+        let pointer = image.read(rva.checked_sub(8)?, 8)?;
+        let locator = read_u64(pointer, 0)
+            .ok()?
+            .checked_sub(image.load_address())?;
+        let locator = u32::try_from(locator).ok()?;
+        let fields = image.read(locator, 0x18)?;
+        let field = |at: usize| read_u32(fields, at).ok();
+        if field(0x0)? != COL_SIGNATURE || field(0x4)? != 0 || field(0x14)? != locator {
+            return None;
+        }
+        let name = image.read(field(0xC)?.checked_add(0x10)?, 0x100)?;
+        let name = &name[..name.iter().position(|&b| b == 0)?];
+        Some(
+            type_descriptor_names(class)
+                .iter()
+                .any(|candidate| candidate.as_bytes() == name),
+        )
+    };
+    is_vtable_of().unwrap_or(false)
+}
+
+/// The address of the vtable of `class` in the running game, i.e. of `CS::CSEzTaskProxy`. See
+/// `find_vtable_rva`.
+///
+/// The address is saved in the address cache, and only searched for again if the RTTI at the cached address
+/// does not lead to `class` anymore.
+pub fn find_vtable(class: &str) -> Result<usize, LiberError> {
+    let image = PeImage::game()?;
+    let rva = cached_game_rva(
+        &image,
+        &format!("vtable {class}"),
+        |rva| is_vtable_of(&image, rva, class),
+        || find_vtable_rva(&image, class),
+    )?;
+    Ok(image.data().as_ptr() as usize + rva as usize)
+}
@@ -1,11 +1,325 @@
-use std::sync::OnceLock;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
 
-#[link(name = "kernel32", kind = "raw-dylib")]
-extern "system" {
-    fn GetModuleHandleA(name: *const u8) -> usize;
+use serde::Deserialize;
+
+use crate::from::details::module::module_provider;
+use crate::from::details::version::GameBuild;
+use crate::{LiberError, Pattern};
+
+/// Base address of the game executable, as found by the current `ModuleProvider`.
+pub fn get_base_address() -> Result<usize, LiberError> {
+    module_provider()
+        .module_base(None)
+        .ok_or(LiberError::ModuleNotFound(None))
+}
+
+/// The symbol database built into the crate, for the game selected with the features of the crate.
+#[cfg(feature = "eldenring")]
+const EMBEDDED_SYMBOLS: &str = include_str!("symbols/eldenring.toml");
+#[cfg(feature = "sekiro")]
+const EMBEDDED_SYMBOLS: &str = include_str!("symbols/sekiro.toml");
+#[cfg(feature = "ds3")]
+const EMBEDDED_SYMBOLS: &str = include_str!("symbols/ds3.toml");
+#[cfg(feature = "ac6")]
+const EMBEDDED_SYMBOLS: &str = include_str!("symbols/ac6.toml");
+
+/// The symbols of one game version, and what identifies the build of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct SymbolSet {
+    /// The link timestamp of the executable.
+    #[serde(default)]
+    timestamp: Option<u32>,
+    /// The file version of the executable, i.e. `2.6.0.0`.
+    #[serde(default)]
+    file_version: Option<String>,
+    symbols: BTreeMap<String, usize>,
+    /// The first bytes of function symbols, as IDA-style patterns, which `SymbolValidator` checks them against.
+    #[serde(default)]
+    prologues: BTreeMap<String, String>,
+}
+
+impl SymbolSet {
+    /// An empty set for `build`, identified by its timestamp and file version.
+    pub fn new(build: &GameBuild) -> Self {
+        Self {
+            timestamp: Some(build.timestamp),
+            file_version: build.file_version.map(|version| version.to_string()),
+            symbols: BTreeMap::new(),
+            prologues: BTreeMap::new(),
+        }
+    }
+    /// Set the RVA of the symbol `name`.
+    pub fn insert(&mut self, name: impl Into<String>, rva: usize) {
+        self.symbols.insert(name.into(), rva);
+    }
+    /// Set the bytes the function `name` starts with.
+    pub fn insert_prologue(&mut self, name: impl Into<String>, prologue: &Pattern) {
+        self.prologues.insert(name.into(), prologue.to_string());
+    }
+    /// The symbols in the set, with their RVAs.
+    pub fn symbols(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.symbols.iter().map(|(name, &rva)| (name.as_str(), rva))
+    }
+    /// Returns `true` if the set is for `build`. Every identifier in the set has to match, and a set without
+    /// identifiers matches no build, so it is only used when it is selected with `set_game_version`.
+    pub fn matches(&self, build: &GameBuild) -> bool {
+        if !self.is_identified() {
+            return false;
+        }
+        self.timestamp
+            .is_none_or(|timestamp| timestamp == build.timestamp)
+            && self.file_version.as_ref().is_none_or(|version| {
+                build
+                    .file_version
+                    .is_some_and(|file_version| file_version.to_string() == *version)
+            })
+    }
+    /// Returns `true` if the set has a timestamp or a file version to detect its build by.
+    pub fn is_identified(&self) -> bool {
+        self.timestamp.is_some() || self.file_version.is_some()
+    }
+    /// The RVA of the symbol `name`.
+    pub fn rva(&self, name: &str) -> Option<usize> {
+        self.symbols.get(name).copied()
+    }
+    /// The bytes the function `name` starts with, if they are known.
+    pub fn prologue(&self, name: &str) -> Option<Pattern> {
+        let prologue = self.prologues.get(name)?;
+        Some(Pattern::new(prologue).expect("prologues are checked when the database is parsed"))
+    }
+}
+
+/// The RVAs of the game functions the crate calls, by game version.
+///
+/// The database is TOML, with a table per version, which holds what identifies the build of that version, and
+/// its symbols:
+///
+/// ```toml
+/// [versions."1.16.0"]
+/// timestamp = 0x64B0A3F2
+/// file_version = "2.6.0.0"
+///
+/// [versions."1.16.0".symbols]
+/// "CS::CSEzTask::register_task" = 0xE71C70
+///
+/// [versions."1.16.0".prologues]
+/// "CS::CSEzTask::register_task" = "48 89 5C 24 08 57 48 83 EC 20"
+/// ```
+///
+/// The `prologues` of a version are optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct SymbolDatabase {
+    #[serde(default)]
+    versions: BTreeMap<String, SymbolSet>,
+}
+
+impl SymbolDatabase {
+    /// Parse a database from TOML.
+    pub fn from_toml(toml: &str) -> Result<Self, LiberError> {
+        let database: Self =
+            toml::from_str(toml).map_err(|e| LiberError::InvalidSymbolDatabase(e.to_string()))?;
+        for (version, symbols) in &database.versions {
+            for (name, prologue) in &symbols.prologues {
+                Pattern::new(prologue).map_err(|e| {
+                    LiberError::InvalidSymbolDatabase(format!("{version}: {name}: {e}"))
+                })?;
+            }
+        }
+        Ok(database)
+    }
+    /// Read a database from a TOML file, i.e. one shipped next to the mod.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, LiberError> {
+        let toml = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            LiberError::InvalidSymbolDatabase(format!("{}: {e}", path.as_ref().display()))
+        })?;
+        Self::from_toml(&toml)
+    }
+    /// The database built into the crate.
+    pub fn embedded() -> &'static Self {
+        static EMBEDDED: OnceLock<SymbolDatabase> = OnceLock::new();
+        EMBEDDED.get_or_init(|| {
+            Self::from_toml(EMBEDDED_SYMBOLS).expect("the embedded symbol database is valid")
+        })
+    }
+    /// The versions in the database.
+    pub fn versions(&self) -> impl Iterator<Item = &str> {
+        self.versions.keys().map(String::as_str)
+    }
+    /// The symbols of `version`.
+    pub fn version(&self, version: &str) -> Result<&SymbolSet, LiberError> {
+        self.versions
+            .get(version)
+            .ok_or_else(|| LiberError::UnsupportedVersion(version.to_string()))
+    }
+    /// The version whose symbol set matches `build`.
+    ///
+    /// Fails with `LiberError::UnsupportedVersion` if none does, instead of guessing, as the RVAs of another
+    /// build lead into random code. Versions without a timestamp and file version never match, they are only
+    /// used when they are selected with `set_game_version`.
+    pub fn version_of(&self, build: &GameBuild) -> Result<&str, LiberError> {
+        self.versions
+            .iter()
+            .find(|(_, symbols)| symbols.matches(build))
+            .map(|(version, _)| version.as_str())
+            .ok_or_else(|| LiberError::UnsupportedVersion(build.to_string()))
+    }
+    /// The RVA of the symbol `name` in `version`.
+    pub fn rva(&self, version: &str, name: &str) -> Result<usize, LiberError> {
+        self.version(version)?
+            .rva(name)
+            .ok_or_else(|| LiberError::MissingSymbol(name.to_string()))
+    }
+    /// Add the symbols of `version`, replacing the ones it had.
+    pub fn insert_version(&mut self, version: impl Into<String>, symbols: SymbolSet) {
+        self.versions.insert(version.into(), symbols);
+    }
+    /// The database as TOML, in the format `from_toml` reads, with the RVAs in hexadecimal.
+    pub fn to_toml(&self) -> String {
+        let key = |key: &str| toml::Value::String(key.to_string()).to_string();
+        let mut toml = String::new();
+        for (version, symbols) in &self.versions {
+            let version = key(version);
+            toml += &format!("[versions.{version}]\n");
+            if let Some(timestamp) = symbols.timestamp {
+                toml += &format!("timestamp = {timestamp:#X}\n");
+            }
+            if let Some(file_version) = &symbols.file_version {
+                toml += &format!("file_version = {}\n", key(file_version));
+            }
+            toml += &format!("\n[versions.{version}.symbols]\n");
+            for (name, rva) in &symbols.symbols {
+                toml += &format!("{} = {rva:#X}\n", key(name));
+            }
+            if !symbols.prologues.is_empty() {
+                toml += &format!("\n[versions.{version}.prologues]\n");
+                for (name, prologue) in &symbols.prologues {
+                    toml += &format!("{} = {}\n", key(name), key(prologue));
+                }
+            }
+            toml += "\n";
+        }
+        toml
+    }
+}
+
+struct ActiveSymbols {
+    /// The database, or `None` for the embedded one.
+    database: Option<&'static SymbolDatabase>,
+    /// The selected version, or `None` until it is detected.
+    version: Option<String>,
+    /// Counts the changes of the symbols that are used, so addresses resolved with older ones are resolved
+    /// again.
+    generation: usize,
+}
+
+impl ActiveSymbols {
+    fn select_version(&mut self, version: String) {
+        if self.version.as_ref() != Some(&version) {
+            self.version = Some(version);
+            self.generation += 1;
+        }
+    }
+}
+
+static SYMBOLS: RwLock<ActiveSymbols> = RwLock::new(ActiveSymbols {
+    database: None,
+    version: None,
+    generation: 0,
+});
+
+/// Replace the symbol database used to resolve symbols, i.e. with one loaded with `SymbolDatabase::from_file`
+/// for a game version the embedded database does not know yet. The database lives for the rest of the
+/// process, and the game version is detected again.
+pub fn set_symbol_database(database: SymbolDatabase) {
+    let database: &'static SymbolDatabase = Box::leak(Box::new(database));
+    let mut symbols = SYMBOLS.write().unwrap_or_else(|e| e.into_inner());
+    symbols.database = Some(database);
+    symbols.version = None;
+    symbols.generation += 1;
+}
+
+/// Read the symbol database at `path` and use it to resolve symbols.
+pub fn load_symbol_database(path: impl AsRef<Path>) -> Result<(), LiberError> {
+    set_symbol_database(SymbolDatabase::from_file(path)?);
+    Ok(())
+}
+
+/// The symbol database used to resolve symbols.
+pub fn symbol_database() -> &'static SymbolDatabase {
+    let symbols = SYMBOLS.read().unwrap_or_else(|e| e.into_inner());
+    symbols.database.unwrap_or_else(SymbolDatabase::embedded)
+}
+
+/// Select the game version whose symbols are used, instead of detecting it.
+pub fn set_game_version(version: &str) -> Result<(), LiberError> {
+    let mut symbols = SYMBOLS.write().unwrap_or_else(|e| e.into_inner());
+    let database = symbols.database.unwrap_or_else(SymbolDatabase::embedded);
+    database.version(version)?;
+    symbols.select_version(version.to_string());
+    Ok(())
+}
+
+/// Detect the build of the running game, and select the symbols of it. Call this at startup, to find out
+/// whether the game is supported before anything is called in it. Otherwise it happens when the first symbol
+/// is resolved.
+///
+/// Fails with `LiberError::UnsupportedVersion` if no version in the symbol database matches the build.
+pub fn detect_game_version() -> Result<String, LiberError> {
+    let build = GameBuild::detect()?;
+    let mut symbols = SYMBOLS.write().unwrap_or_else(|e| e.into_inner());
+    let database = symbols.database.unwrap_or_else(SymbolDatabase::embedded);
+    let version = database.version_of(&build)?.to_string();
+    symbols.select_version(version.clone());
+    Ok(version)
+}
+
+/// Changes whenever other symbols are selected, so `liber_fn!` knows to resolve its symbol again.
+#[doc(hidden)]
+pub fn symbols_generation() -> usize {
+    SYMBOLS.read().unwrap_or_else(|e| e.into_inner()).generation
+}
+
+/// The game version whose symbols are used, if it was detected or selected yet.
+pub fn game_version() -> Option<String> {
+    SYMBOLS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .version
+        .clone()
+}
+
+/// The address of the symbol `name` in the running game, from the RVA in the symbol database for the game
+/// version. The version is detected first, if it was not yet.
+pub fn resolve_symbol(name: &str) -> Result<usize, LiberError> {
+    let version = match game_version() {
+        Some(version) => version,
+        None => detect_game_version()?,
+    };
+    let rva = symbol_database().rva(&version, name)?;
+    Ok(get_base_address()? + rva)
 }
 
-pub fn get_base_address() -> usize {
-    static BASE_ADDRESS: OnceLock<usize> = OnceLock::new();
-    *BASE_ADDRESS.get_or_init(|| unsafe { GetModuleHandleA(std::ptr::null::<u8>()) })
+/// A function in the symbol database, with the signature it has in the game. A `liber_fn!` declared with
+/// `symbol` has to have exactly this signature, or it does not compile.
+///
+/// ```compile_fail
+/// # use liber_rs::{liber_fn, FunctionSymbol};
+/// struct Add;
+///
+/// impl FunctionSymbol for Add {
+///     const NAME: &'static str = "MyMod::add";
+///     type Fn = extern "C" fn(u32, u32) -> u32;
+/// }
+///
+/// liber_fn! {
+///     unsafe fn add(a: u32) -> u32 = symbol(Add);
+/// }
+/// ```
+pub trait FunctionSymbol {
+    /// The name of the symbol in the symbol database.
+    const NAME: &'static str;
+    /// The `extern "C" fn` type of the function.
+    type Fn: Copy;
 }
@@ -0,0 +1,1107 @@
+#![cfg(test)]
+
+use crate::from::details::cache::cached_game_rva;
+use crate::from::details::module::maps_module_base;
+use crate::tests::GAME_SYMBOLS;
+use crate::{
+    decode, detect_game_version, find_vtable_rva, game_version, get_base_address, is_vtable_of,
+    resolve_symbol, set_address_cache_path, set_game_version, set_module_provider,
+    set_symbol_database, symbols_generation, AddressCache, ChainBase, ChainStep, DataDirectory,
+    Export, FakeMemory, FileVersion, GameBuild, ImageLayout, Import, ImportSymbol, LiberError,
+    MockModuleProvider, ModuleProvider, Pattern, PeImage, PointerChain, RuntimeFunction,
+    SymbolDatabase, SymbolKind, SymbolSet, SymbolValidator,
+};
+use std::sync::PoisonError;
+
+const MAPS: &str = "\
+140000000-140001000 r--p 00000000 00:2a 1234                       /games/ELDEN RING/eldenring.exe
+140001000-145000000 r-xp 00001000 00:2a 1234                       /games/ELDEN RING/eldenring.exe
+7f0000000000-7f0000001000 rw-p 00000000 00:00 0
+7f0000002000-7f0000003000 r--p 00000000 08:01 5678                       /usr/lib/libc.so.6
+7f0000001000-7f0000002000 r--p 00000000 08:01 5678                       /usr/lib/libc.so.6
+";
+
+#[test]
+fn maps_module_base_finds_lowest_mapping() {
+    let base = maps_module_base(MAPS, |path| path.ends_with("/eldenring.exe"));
+    assert_eq!(base, Some(0x140000000));
+    let base = maps_module_base(MAPS, |path| path.rsplit('/').next() == Some("libc.so.6"));
+    assert_eq!(base, Some(0x7f0000001000));
+    assert_eq!(maps_module_base(MAPS, |path| path == "missing"), None);
+}
+
+#[test]
+fn mock_module_provider() {
+    let provider =
+        MockModuleProvider::with_base(0x140000000).with_module("eldenring.exe", 0x150000000);
+    assert_eq!(provider.module_base(None), Some(0x140000000));
+    assert_eq!(
+        provider.module_base(Some("EldenRing.exe")),
+        Some(0x150000000)
+    );
+    assert_eq!(provider.module_base(Some("kernel32.dll")), None);
+    assert_eq!(MockModuleProvider::new().module_base(None), None);
+}
+
+const SYMBOLS: &str = r#"
+[versions."1.0.0"]
+timestamp = 0x64A00000
+
+[versions."1.0.0".symbols]
+"CS::CSEzTask::register_task" = 0x1000
+
+[versions."1.1.0"]
+timestamp = 0x64B00000
+file_version = "2.6.0.0"
+
+[versions."1.1.0".symbols]
+"CS::CSEzTask::register_task" = 0x2000
+"CS::CSEzTask::free_task" = 0x3000
+
+[versions.manual.symbols]
+"CS::CSEzTask::register_task" = 0x4000
+"#;
+
+#[test]
+fn symbol_database_maps_names_to_rvas() {
+    let database = SymbolDatabase::from_toml(SYMBOLS).unwrap();
+    assert_eq!(
+        database.versions().collect::<Vec<_>>(),
+        ["1.0.0", "1.1.0", "manual"]
+    );
+    assert_eq!(database.rva("1.1.0", "CS::CSEzTask::free_task"), Ok(0x3000));
+    assert_eq!(
+        database.rva("1.0.0", "CS::CSEzTask::free_task"),
+        Err(LiberError::MissingSymbol("CS::CSEzTask::free_task".into()))
+    );
+    assert_eq!(
+        database.rva("0.9.0", "CS::CSEzTask::free_task"),
+        Err(LiberError::UnsupportedVersion("0.9.0".into()))
+    );
+    assert!(matches!(
+        SymbolDatabase::from_toml("[versions.\"1.0.0\"]"),
+        Err(LiberError::InvalidSymbolDatabase(_))
+    ));
+}
+
+#[test]
+fn symbol_sets_are_selected_by_game_build() {
+    let database = SymbolDatabase::from_toml(SYMBOLS).unwrap();
+    let mut build = GameBuild {
+        timestamp: IMAGE_TIMESTAMP,
+        file_version: Some(FileVersion {
+            major: 2,
+            minor: 6,
+            build: 0,
+            revision: 0,
+        }),
+    };
+    assert_eq!(database.version_of(&build), Ok("1.1.0"));
+    build.timestamp = 0x64A00000;
+    assert_eq!(database.version_of(&build), Ok("1.0.0"));
+    build.timestamp = IMAGE_TIMESTAMP;
+    build.file_version = None;
+    // Versions without identifiers are not used for unknown builds.
+    assert_eq!(
+        database.version_of(&build),
+        Err(LiberError::UnsupportedVersion(
+            "timestamp 0x64b00000, no file version".into()
+        ))
+    );
+}
+
+#[test]
+fn symbol_database_is_written_as_toml() {
+    let build = GameBuild {
+        timestamp: IMAGE_TIMESTAMP,
+        file_version: None,
+    };
+    let mut symbols = SymbolSet::new(&build);
+    symbols.insert("CS::CSEzTask::register_task", 0xE71C70);
+    let prologue = Pattern::new("48 89 5C 24 ?? 57").unwrap();
+    symbols.insert_prologue("CS::CSEzTask::register_task", &prologue);
+    let mut database = SymbolDatabase::from_toml(SYMBOLS).unwrap();
+    database.insert_version("1.2.0", symbols);
+    let toml = database.to_toml();
+    assert!(toml.contains(
+        "[versions.\"1.2.0\"]\ntimestamp = 0x64B00000\n\n[versions.\"1.2.0\".symbols]\n\"CS::CSEzTask::register_task\" = 0xE71C70\n\n[versions.\"1.2.0\".prologues]\n\"CS::CSEzTask::register_task\" = \"48 89 5C 24 ?? 57\"\n"
+    ));
+    assert_eq!(SymbolDatabase::from_toml(&toml), Ok(database.clone()));
+    assert_eq!(
+        database
+            .version("1.2.0")
+            .unwrap()
+            .prologue("CS::CSEzTask::register_task"),
+        Some(prologue)
+    );
+    assert!(matches!(
+        SymbolDatabase::from_toml(&toml.replace("?? 57", "5")),
+        Err(LiberError::InvalidSymbolDatabase(_))
+    ));
+}
+
+#[test]
+fn embedded_symbol_database_has_every_symbol() {
+    let database = SymbolDatabase::embedded();
+    for version in database.versions() {
+        for name in ["CS::CSEzTask::register_task", "CS::CSEzTask::free_task"] {
+            assert!(database.rva(version, name).is_ok(), "{name} in {version}");
+        }
+    }
+}
+
+#[test]
+fn game_build_is_read_from_image() {
+    let data = synthetic_image(
+        ImageLayout::Mapped,
+        &[(".rsrc", 0x1000, &version_resource(0x1000, [2, 6, 0, 0]))],
+        &[(DataDirectory::RESOURCE, 0x1000, 0x100)],
+    );
+    let image = PeImage::new(&data, ImageLayout::Mapped).unwrap();
+    let build = GameBuild::of(&image).unwrap();
+    assert_eq!(build.timestamp, IMAGE_TIMESTAMP);
+    assert_eq!(build.file_version.unwrap().to_string(), "2.6.0.0");
+
+    let data = synthetic_image(ImageLayout::Mapped, &[(".text", 0x1000, &[0xCC])], &[]);
+    let image = PeImage::new(&data, ImageLayout::Mapped).unwrap();
+    assert_eq!(GameBuild::of(&image).unwrap().file_version, None);
+}
+
+#[test]
+fn symbols_are_resolved_for_detected_version() {
+    let _symbols = GAME_SYMBOLS.lock().unwrap_or_else(PoisonError::into_inner);
+    let image = synthetic_image(
+        ImageLayout::Mapped,
+        &[(".rsrc", 0x1000, &version_resource(0x1000, [2, 6, 0, 0]))],
+        &[(DataDirectory::RESOURCE, 0x1000, 0x100)],
+    );
+    let base = image.leak().as_ptr() as usize;
+    set_module_provider(MockModuleProvider::with_base(base));
+    assert_eq!(get_base_address(), Ok(base));
+    assert_eq!(game_version(), None);
+    // The embedded database does not know the build, so nothing is resolved until a version is selected.
+    #[cfg(feature = "eldenring")]
+    {
+        assert!(matches!(
+            resolve_symbol("CS::CSEzTask::register_task"),
+            Err(LiberError::UnsupportedVersion(_))
+        ));
+        assert_eq!(game_version(), None);
+        set_game_version("legacy").unwrap();
+        assert_eq!(
+            resolve_symbol("CS::CSEzTask::register_task"),
+            Ok(base + 0xE71C70)
+        );
+    }
+
+    let generation = symbols_generation();
+    set_symbol_database(SymbolDatabase::from_toml(SYMBOLS).unwrap());
+    assert!(symbols_generation() > generation);
+    assert_eq!(game_version(), None);
+    assert_eq!(
+        resolve_symbol("CS::CSEzTask::register_task"),
+        Ok(base + 0x2000)
+    );
+    assert_eq!(game_version().as_deref(), Some("1.1.0"));
+    assert_eq!(detect_game_version(), Ok("1.1.0".to_string()));
+    assert_eq!(
+        set_game_version("0.9.0"),
+        Err(LiberError::UnsupportedVersion("0.9.0".into()))
+    );
+    let generation = symbols_generation();
+    set_game_version("manual").unwrap();
+    assert_eq!(game_version().as_deref(), Some("manual"));
+    // Addresses resolved by `liber_fn!` are resolved again for the other version, but not for the same one.
+    assert!(symbols_generation() > generation);
+    let generation = symbols_generation();
+    set_game_version("manual").unwrap();
+    assert_eq!(symbols_generation(), generation);
+    assert_eq!(
+        resolve_symbol("CS::CSEzTask::register_task"),
+        Ok(base + 0x4000)
+    );
+
+    // Symbols are validated in the running game, with the prologues of the database, or where a resolver
+    // finds them.
+    let mut text = vec![0xCC; 0x20];
+    put(&mut text, 0x0, &[0x48, 0x89, 0x5C, 0x24, 0x08, 0xC3]);
+    put(&mut text, 0x10, &[0x40, 0x53, 0xC3]);
+    let mut pdata = Vec::new();
+    for (i, (begin, end)) in [(0x1000u32, 0x1006u32), (0x1010, 0x1013)]
+        .iter()
+        .enumerate()
+    {
+        put(&mut pdata, i as u32 * 12, &begin.to_le_bytes());
+        put(&mut pdata, i as u32 * 12 + 4, &end.to_le_bytes());
+    }
+    let image = synthetic_image(
+        ImageLayout::Mapped,
+        &[(".text", 0x1000, &text), (".pdata", 0x2000, &pdata)],
+        &[(DataDirectory::EXCEPTION, 0x2000, 24)],
+    );
+    let base = image.leak().as_ptr() as usize;
+    set_module_provider(MockModuleProvider::with_base(base));
+    set_symbol_database(
+        SymbolDatabase::from_toml(
+            r#"
+            [versions.validated]
+            timestamp = 0x64B00000
+
+            [versions.validated.symbols]
+            first = 0x1000
+            second = 0x1010
+            inside = 0x1002
+            "CS::CSEzTask::register_task" = 0x1000
+
+            [versions.validated.prologues]
+            first = "48 89 5C 24 ??"
+            second = "48 89 5C 24 ??"
+            "#,
+        )
+        .unwrap(),
+    );
+    let any_function = SymbolKind::Function { prologue: None };
+    let report = SymbolValidator::empty()
+        .function("first")
+        .function("second")
+        .function("inside")
+        .resolved("found", any_function.clone(), move || Ok(base + 0x1010))
+        .resolved("elsewhere", any_function, || Ok(0x10))
+        .validate();
+    let results: Vec<_> = report
+        .symbols
+        .into_iter()
+        .map(|symbol| symbol.result)
+        .collect();
+    assert_eq!(
+        results,
+        [
+            Ok(0x1000),
+            Err(LiberError::PrologueMismatch("second".into())),
+            Err(LiberError::NotFunctionStart("inside".into())),
+            Ok(0x1010),
+            Err(LiberError::InvalidImage(
+                "0x10 is not in the game executable".into()
+            )),
+        ]
+    );
+
+    set_module_provider(MockModuleProvider::new());
+    assert_eq!(get_base_address(), Err(LiberError::ModuleNotFound(None)));
+    assert_eq!(
+        resolve_symbol("CS::CSEzTask::register_task"),
+        Err(LiberError::ModuleNotFound(None))
+    );
+}
+
+#[cfg(feature = "eldenring")]
+#[test]
+fn eldenring_has_task_groups_and_symbols() {
+    use crate::from::CS::CSTaskGroup;
+    let database = SymbolDatabase::embedded();
+    assert_eq!(database.versions().collect::<Vec<_>>(), ["legacy"]);
+    assert_eq!(
+        database.rva("legacy", "CS::CSEzTask::register_task"),
+        Ok(0xE71C70)
+    );
+    assert_eq!(
+        database.rva("legacy", "CS::CSEzTask::free_task"),
+        Ok(0xE71D60)
+    );
+    assert!(CSTaskGroup::FrameBegin.is_valid());
+    assert!(CSTaskGroup::FrameEnd.is_valid());
+    assert!(!CSTaskGroup::INVALID.is_valid());
+    assert!(!CSTaskGroup::SIZE.is_valid());
+    assert_eq!(CSTaskGroup::FrameEnd as i32 + 1, CSTaskGroup::SIZE as i32);
+}
+
+#[cfg(not(feature = "eldenring"))]
+#[test]
+fn other_games_register_tasks_by_index_without_symbols() {
+    use crate::from::CS::CSTaskGroup;
+    assert_eq!(SymbolDatabase::embedded().versions().count(), 0);
+    assert!(CSTaskGroup::FrameBegin.is_valid());
+    assert!(CSTaskGroup(42).is_valid());
+    assert!(!CSTaskGroup::INVALID.is_valid());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn native_module_provider_finds_executable() {
+    let provider = crate::NativeModuleProvider::new();
+    assert!(provider.module_base(None).is_some());
+    let exe = std::env::current_exe().unwrap();
+    assert_eq!(crate::current_module_path(), Some(exe));
+}
+
+pub(crate) const IMAGE_TIMESTAMP: u32 = 0x64B0_0000;
+
+/// A PE32+ image with `sections` at their RVAs, and `directories` as `(index, rva, size)`.
+pub(crate) fn synthetic_image(
+    layout: ImageLayout,
+    sections: &[(&str, u32, &[u8])],
+    directories: &[(usize, u32, u32)],
+) -> Vec<u8> {
+    let size_of_image = sections
+        .iter()
+        .map(|&(_, rva, data)| rva as usize + data.len())
+        .max()
+        .unwrap_or(0x1000)
+        .next_multiple_of(0x1000);
+    let mut raw_offsets = Vec::new();
+    let mut raw_end = 0x400;
+    for &(_, rva, data) in sections {
+        raw_offsets.push(match layout {
+            ImageLayout::Mapped => rva as usize,
+            ImageLayout::File => raw_end,
+        });
+        raw_end = (raw_end + data.len()).next_multiple_of(0x200);
+    }
+    let mut image = vec![
+        0;
+        match layout {
+            ImageLayout::Mapped => size_of_image,
+            ImageLayout::File => raw_end,
+        }
+    ];
+    let mut put =
+        |offset: usize, bytes: &[u8]| image[offset..offset + bytes.len()].copy_from_slice(bytes);
+    put(0, b"MZ");
+    put(0x3C, &0x80u32.to_le_bytes());
+    put(0x80, b"PE\0\0");
+    put(0x84, &0x8664u16.to_le_bytes());
+    put(0x86, &(sections.len() as u16).to_le_bytes());
+    put(0x88, &IMAGE_TIMESTAMP.to_le_bytes());
+    put(0x94, &0xF0u16.to_le_bytes());
+    put(0x98, &0x20Bu16.to_le_bytes());
+    put(0x98 + 0x18, &0x140000000u64.to_le_bytes());
+    put(0x98 + 0x38, &(size_of_image as u32).to_le_bytes());
+    put(0x98 + 0x3C, &0x400u32.to_le_bytes());
+    put(0x98 + 0x6C, &16u32.to_le_bytes());
+    for &(index, rva, size) in directories {
+        put(0x98 + 0x70 + index * 8, &rva.to_le_bytes());
+        put(0x98 + 0x74 + index * 8, &size.to_le_bytes());
+    }
+    for (i, &(name, rva, data)) in sections.iter().enumerate() {
+        let header = 0x188 + i * 40;
+        put(header, name.as_bytes());
+        put(header + 0x8, &(data.len() as u32).to_le_bytes());
+        put(header + 0xC, &rva.to_le_bytes());
+        put(header + 0x10, &(data.len() as u32).to_le_bytes());
+        put(header + 0x14, &(raw_offsets[i] as u32).to_le_bytes());
+        put(raw_offsets[i], data);
+    }
+    image
+}
+
+/// Put `bytes` at `offset` in `section`, growing it as needed.
+fn put(section: &mut Vec<u8>, offset: u32, bytes: &[u8]) {
+    let offset = offset as usize;
+    if section.len() < offset + bytes.len() {
+        section.resize(offset + bytes.len(), 0);
+    }
+    section[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// An image with a `.text` section, exports and imports in `.rdata`, and an exception directory in `.pdata`.
+fn image_with_directories(layout: ImageLayout) -> Vec<u8> {
+    let text = vec![0xCC; 0x30];
+    let mut rdata = Vec::new();
+    // Export directory at 0x2000, with an unnamed export, a named one and a forwarder.
+    put(&mut rdata, 0x10, &1u32.to_le_bytes());
+    put(&mut rdata, 0x14, &3u32.to_le_bytes());
+    put(&mut rdata, 0x18, &2u32.to_le_bytes());
+    put(&mut rdata, 0x1C, &0x2040u32.to_le_bytes());
+    put(&mut rdata, 0x20, &0x2050u32.to_le_bytes());
+    put(&mut rdata, 0x24, &0x2060u32.to_le_bytes());
+    for (i, rva) in [0x1000u32, 0x1010, 0x2070].iter().enumerate() {
+        put(&mut rdata, 0x40 + i as u32 * 4, &rva.to_le_bytes());
+    }
+    put(&mut rdata, 0x50, &0x2090u32.to_le_bytes());
+    put(&mut rdata, 0x54, &0x20A0u32.to_le_bytes());
+    put(&mut rdata, 0x60, &1u16.to_le_bytes());
+    put(&mut rdata, 0x62, &2u16.to_le_bytes());
+    put(&mut rdata, 0x70, b"NTDLL.RtlAllocateHeap\0");
+    put(&mut rdata, 0x90, b"liber_export\0");
+    put(&mut rdata, 0xA0, b"liber_forward\0");
+    // Import directory at 0x2100, with one module and a terminating descriptor.
+    put(&mut rdata, 0x100, &0x2140u32.to_le_bytes());
+    put(&mut rdata, 0x10C, &0x2180u32.to_le_bytes());
+    put(&mut rdata, 0x110, &0x2160u32.to_le_bytes());
+    for table in [0x140, 0x160] {
+        put(&mut rdata, table, &0x21A0u64.to_le_bytes());
+        put(&mut rdata, table + 8, &(1u64 << 63 | 7).to_le_bytes());
+        put(&mut rdata, table + 16, &0u64.to_le_bytes());
+    }
+    put(&mut rdata, 0x180, b"KERNEL32.dll\0");
+    put(&mut rdata, 0x1A2, b"GetModuleHandleA\0");
+    let mut pdata = Vec::new();
+    for (i, function) in [[0x1000u32, 0x1010, 0x2200], [0x1010, 0x1020, 0x2210]]
+        .iter()
+        .enumerate()
+    {
+        for (j, value) in function.iter().enumerate() {
+            put(&mut pdata, (i * 12 + j * 4) as u32, &value.to_le_bytes());
+        }
+    }
+    synthetic_image(
+        layout,
+        &[
+            (".text", 0x1000, &text),
+            (".rdata", 0x2000, &rdata),
+            (".pdata", 0x3000, &pdata),
+        ],
+        &[
+            (DataDirectory::EXPORT, 0x2000, 0x100),
+            (DataDirectory::IMPORT, 0x2100, 0x28),
+            (DataDirectory::EXCEPTION, 0x3000, 24),
+        ],
+    )
+}
+
+#[test]
+fn pe_headers_and_directories_are_parsed() {
+    for layout in [ImageLayout::Mapped, ImageLayout::File] {
+        let data = image_with_directories(layout);
+        let image = PeImage::new(&data, layout).unwrap();
+        assert_eq!(image.machine(), 0x8664);
+        assert_eq!(image.timestamp(), IMAGE_TIMESTAMP);
+        assert_eq!(image.image_base(), 0x140000000);
+        assert_eq!(image.size_of_image(), 0x4000);
+        assert_eq!(image.data_directory(DataDirectory::RESOURCE), None);
+        assert_eq!(
+            image.section(".rdata").unwrap().virtual_address,
+            0x2000,
+            "{layout:?}"
+        );
+
+        let exports = image.exports().unwrap();
+        assert_eq!(
+            exports,
+            [
+                Export {
+                    name: None,
+                    ordinal: 1,
+                    rva: 0x1000,
+                    forwarder: None
+                },
+                Export {
+                    name: Some("liber_export".into()),
+                    ordinal: 2,
+                    rva: 0x1010,
+                    forwarder: None
+                },
+                Export {
+                    name: Some("liber_forward".into()),
+                    ordinal: 3,
+                    rva: 0,
+                    forwarder: Some("NTDLL.RtlAllocateHeap".into())
+                },
+            ]
+        );
+        assert_eq!(image.export("liber_export"), Ok(0x1010));
+        assert_eq!(
+            image.export("liber_forward"),
+            Err(LiberError::MissingSymbol("liber_forward".into()))
+        );
+
+        assert_eq!(
+            image.imports().unwrap(),
+            [
+                Import {
+                    module: "KERNEL32.dll".into(),
+                    symbol: ImportSymbol::Name("GetModuleHandleA".into()),
+                    address_rva: 0x2160,
+                },
+                Import {
+                    module: "KERNEL32.dll".into(),
+                    symbol: ImportSymbol::Ordinal(7),
+                    address_rva: 0x2168,
+                },
+            ]
+        );
+
+        assert_eq!(image.runtime_functions().unwrap().len(), 2);
+        assert_eq!(
+            image.function_containing(0x1015),
+            Ok(Some(RuntimeFunction {
+                begin: 0x1010,
+                end: 0x1020,
+                unwind_info: 0x2210
+            }))
+        );
+        assert_eq!(image.function_containing(0x1020), Ok(None));
+    }
+}
+
+#[test]
+fn corrupt_images_fail_instead_of_overflowing() {
+    fn invalid<T>(result: Result<T, LiberError>) -> bool {
+        matches!(result, Err(LiberError::InvalidImage(_)))
+    }
+    // Directories at the end of the address space, whose fields are past it.
+    let data = synthetic_image(
+        ImageLayout::Mapped,
+        &[(".text", 0x1000, &[0xCC; 0x10])],
+        &[
+            (DataDirectory::EXPORT, 0xFFFF_FFF8, 0x28),
+            (DataDirectory::IMPORT, 0xFFFF_FFF8, 0x28),
+            (DataDirectory::RESOURCE, 0xFFFF_FFF8, 0x10),
+            (DataDirectory::EXCEPTION, 0xFFFF_FFF8, 24),
+        ],
+    );
+    let image = PeImage::new(&data, ImageLayout::Mapped).unwrap();
+    assert!(invalid(image.exports()));
+    assert!(invalid(image.imports()));
+    assert!(invalid(image.resource(16)));
+    assert!(invalid(image.file_version()));
+    assert!(invalid(image.runtime_functions()));
+
+    // An import by name at the last RVA, whose hint is before its name.
+    let mut rdata = Vec::new();
+    put(&mut rdata, 0x0, &0x2040u32.to_le_bytes());
+    put(&mut rdata, 0xC, &0x2060u32.to_le_bytes());
+    put(&mut rdata, 0x10, &0x2040u32.to_le_bytes());
+    put(&mut rdata, 0x40, &0xFFFF_FFFFu64.to_le_bytes());
+    put(&mut rdata, 0x60, b"KERNEL32.dll\0");
+    let data = synthetic_image(
+        ImageLayout::Mapped,
+        &[(".rdata", 0x2000, &rdata)],
+        &[(DataDirectory::IMPORT, 0x2000, 0x28)],
+    );
+    let image = PeImage::new(&data, ImageLayout::Mapped).unwrap();
+    assert!(invalid(image.imports()));
+
+    // A section whose raw data would be past the end of the address space.
+    let mut data = synthetic_image(ImageLayout::File, &[(".text", 0x1000, &[0xCC; 0x10])], &[]);
+    data[0x188 + 0x14..0x188 + 0x18].copy_from_slice(&0xFFFF_FFF8u32.to_le_bytes());
+    let image = PeImage::new(&data, ImageLayout::File).unwrap();
+    assert_eq!(image.rva_to_offset(0x1008), None);
+    assert_eq!(image.read(0x1008, 1), None);
+
+    // A type descriptor in a section at the end of the address space.
+    let mut rdata = vec![0; 0x10];
+    rdata.extend(b".?AVCSEzTask@CS@@\0");
+    let mut data = synthetic_image(ImageLayout::File, &[(".rdata", 0x1000, &rdata)], &[]);
+    data[0x188 + 0xC..0x188 + 0x10].copy_from_slice(&0xFFFF_FFF8u32.to_le_bytes());
+    let image = PeImage::new(&data, ImageLayout::File).unwrap();
+    assert!(invalid(find_vtable_rva(&image, "CS::CSEzTask")));
+}
+
+#[test]
+fn patterns_parse_signatures_and_masks() {
+    let pattern = Pattern::new("48 8b ?? ? E8").unwrap();
+    assert_eq!(pattern.len(), 5);
+    assert_eq!(pattern.to_string(), "48 8B ?? ?? E8");
+    assert_eq!(
+        Pattern::from_mask(b"\x48\x8B\x00\x00\xE8", "xx??x"),
+        Ok(pattern)
+    );
+    for invalid in ["", "?? ??", "48 8G", "488B"] {
+        assert_eq!(
+            Pattern::new(invalid),
+            Err(LiberError::InvalidPattern(invalid.to_string()))
+        );
+    }
+    assert!(Pattern::from_mask(b"\x48", "xx").is_err());
+}
+
+#[test]
+fn patterns_find_matches() {
+    let haystack = [
+        0x90, 0x48, 0x8B, 0x01, 0x02, 0xE8, 0x48, 0x8B, 0x03, 0x04, 0xE8, 0x48,
+    ];
+    let pattern = Pattern::new("48 8B ?? ?? E8").unwrap();
+    assert_eq!(pattern.find(&haystack), Some(1));
+    assert_eq!(pattern.find_all(&haystack), vec![1, 6]);
+    assert_eq!(
+        pattern.find_unique(&haystack),
+        Err(LiberError::PatternNotUnique("48 8B ?? ?? E8".into(), 2))
+    );
+    let pattern = Pattern::new("?? 8B 03").unwrap();
+    assert_eq!(pattern.find_unique(&haystack), Ok(6));
+    let pattern = Pattern::new("8B 05").unwrap();
+    assert_eq!(pattern.find(&haystack), None);
+    assert_eq!(
+        pattern.find_unique(&haystack),
+        Err(LiberError::PatternNotFound("8B 05".into()))
+    );
+    assert_eq!(Pattern::new("48 8B").unwrap().find(&[0x48]), None);
+}
+
+#[test]
+fn parallel_scan_finds_matches_across_chunks() {
+    let haystack: Vec<u8> = (0..100_003u32).map(|i| (i % 251) as u8).collect();
+    let pattern = Pattern::new("F9 FA ?? 01").unwrap();
+    let matches = pattern.find_all_with_threads(&haystack, 1);
+    assert_eq!(matches.len(), 398);
+    for threads in [2, 7, 64] {
+        assert_eq!(pattern.find_all_with_threads(&haystack, threads), matches);
+    }
+}
+
+#[test]
+fn pe_sections_are_scanned() {
+    let image = synthetic_image(
+        ImageLayout::Mapped,
+        &[
+            (".text", 0x1000, &[0xCC, 0x48, 0x8B, 0x05, 0xE8]),
+            (".rdata", 0x2000, &[0x48, 0x8B, 0x05, 0xE8]),
+        ],
+        &[],
+    );
+    let image = PeImage::new(&image, ImageLayout::Mapped).unwrap();
+    let names: Vec<_> = image
+        .sections()
+        .map(|section| section.name().to_string())
+        .collect();
+    assert_eq!(names, [".text", ".rdata"]);
+    let pattern = Pattern::new("48 8B ?? E8").unwrap();
+    assert_eq!(pattern.find_in_section(&image, ".text"), Ok(0x1001));
+    assert_eq!(pattern.find_in_section(&image, ".rdata"), Ok(0x2000));
+    assert!(matches!(
+        pattern.find_in_section(&image, ".data"),
+        Err(LiberError::InvalidImage(_))
+    ));
+    assert!(PeImage::new(&[0; 0x40], ImageLayout::File).is_err());
+}
+
+/// A `.rsrc` section at `rva` with a `VS_VERSIONINFO` resource of `version`.
+fn version_resource(rva: u32, version: [u16; 4]) -> Vec<u8> {
+    let mut rsrc = Vec::new();
+    // One entry on each level of the directory: `RT_VERSION`, ID 1, and the language.
+    for (table, id, offset) in [
+        (0x0, 16, 0x8000_0018u32),
+        (0x18, 1, 0x8000_0030),
+        (0x30, 0x409, 0x48),
+    ] {
+        put(&mut rsrc, table + 0xE, &1u16.to_le_bytes());
+        put(&mut rsrc, table + 0x10, &(id as u32).to_le_bytes());
+        put(&mut rsrc, table + 0x14, &offset.to_le_bytes());
+    }
+    put(&mut rsrc, 0x48, &(rva + 0x60).to_le_bytes());
+    put(&mut rsrc, 0x4C, &0x5Cu32.to_le_bytes());
+    put(&mut rsrc, 0x60, &0x5Cu16.to_le_bytes());
+    put(&mut rsrc, 0x62, &0x34u16.to_le_bytes());
+    let key: Vec<u8> = "VS_VERSION_INFO\0"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    put(&mut rsrc, 0x66, &key);
+    put(&mut rsrc, 0x88, &0xFEEF04BDu32.to_le_bytes());
+    put(&mut rsrc, 0x8C, &0x10000u32.to_le_bytes());
+    put(
+        &mut rsrc,
+        0x90,
+        &((version[0] as u32) << 16 | version[1] as u32).to_le_bytes(),
+    );
+    put(
+        &mut rsrc,
+        0x94,
+        &((version[2] as u32) << 16 | version[3] as u32).to_le_bytes(),
+    );
+    put(&mut rsrc, 0xBB, &[0]);
+    rsrc
+}
+
+/// A file image with the RTTI of `CSEzTask`, of its subobject at offset 8, and of `FD4TaskBase`, and their
+/// vtables at 0x2148, 0x2108 and 0x2188.
+fn rtti_image() -> Vec<u8> {
+    let base = 0x140000000u64;
+    let mut data = Vec::new();
+    put(&mut data, 0x10, b".?AVCSEzTask@CS@@\0");
+    put(&mut data, 0x50, b".?AUFD4TaskBase@FD4@@\0");
+    let mut rdata = Vec::new();
+    for (locator, offset, descriptor) in [(0x0, 0, 0x3000), (0x18, 8, 0x3000), (0x30, 0, 0x3040)] {
+        put(&mut rdata, locator, &1u32.to_le_bytes());
+        put(&mut rdata, locator + 0x4, &(offset as u32).to_le_bytes());
+        put(
+            &mut rdata,
+            locator + 0xC,
+            &(descriptor as u32).to_le_bytes(),
+        );
+        put(
+            &mut rdata,
+            locator + 0x14,
+            &(0x2000 + locator).to_le_bytes(),
+        );
+    }
+    for (pointer, locator) in [(0x100, 0x2018), (0x140, 0x2000), (0x180, 0x2030)] {
+        put(&mut rdata, pointer, &(base + locator).to_le_bytes());
+        put(&mut rdata, pointer + 8, &(base + 0x1000).to_le_bytes());
+    }
+    synthetic_image(
+        ImageLayout::File,
+        &[
+            (".text", 0x1000, &[0xCC; 0x10]),
+            (".rdata", 0x2000, &rdata),
+            (".data", 0x3000, &data),
+        ],
+        &[],
+    )
+}
+
+#[test]
+fn vtables_are_found_through_rtti() {
+    let file = rtti_image();
+    let image = PeImage::new(&file, ImageLayout::File).unwrap();
+    assert_eq!(find_vtable_rva(&image, "CS::CSEzTask"), Ok(0x2148));
+    assert_eq!(find_vtable_rva(&image, ".?AVCSEzTask@CS@@"), Ok(0x2148));
+    assert_eq!(find_vtable_rva(&image, "FD4::FD4TaskBase"), Ok(0x2188));
+    assert_eq!(
+        find_vtable_rva(&image, "CS::CSEzTaskProxy"),
+        Err(LiberError::MissingSymbol("CS::CSEzTaskProxy".into()))
+    );
+    assert!(is_vtable_of(&image, 0x2148, "CS::CSEzTask"));
+    assert!(!is_vtable_of(&image, 0x2148, "FD4::FD4TaskBase"));
+    assert!(!is_vtable_of(&image, 0x2108, "CS::CSEzTask"));
+    assert!(!is_vtable_of(&image, 0x1000, "CS::CSEzTask"));
+}
+
+#[test]
+fn address_cache_is_validated_and_keyed_by_image() {
+    let file = rtti_image();
+    let image = PeImage::new(&file, ImageLayout::File).unwrap();
+    let path =
+        std::env::temp_dir().join(format!("liber-address-cache-{}.toml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut cache = AddressCache::load(&path, &image);
+    assert_eq!(cache, AddressCache::new(&image));
+    let validate = |rva| is_vtable_of(&image, rva, "CS::CSEzTask");
+    let resolve = || find_vtable_rva(&image, "CS::CSEzTask");
+    assert_eq!(
+        cache.rva(&image, "vtable CS::CSEzTask", validate, resolve),
+        Ok((0x2148, true))
+    );
+    cache.save(&path).unwrap();
+
+    let mut cache = AddressCache::load(&path, &image);
+    assert_eq!(cache.get("vtable CS::CSEzTask"), Some(0x2148));
+    let unresolved = || -> Result<u32, LiberError> { panic!("the cached RVA is valid") };
+    assert_eq!(
+        cache.rva(&image, "vtable CS::CSEzTask", validate, unresolved),
+        Ok((0x2148, false))
+    );
+    // A cached RVA that does not validate is resolved again.
+    assert_eq!(
+        cache.rva(&image, "vtable CS::CSEzTask", |_| false, resolve),
+        Ok((0x2148, true))
+    );
+
+    // Another build of the executable does not use the cache.
+    let mut other = file.clone();
+    other[0x88] ^= 1;
+    let other = PeImage::new(&other, ImageLayout::File).unwrap();
+    assert_eq!(
+        AddressCache::load(&path, &other).get("vtable CS::CSEzTask"),
+        None
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    // The cache of the game is not locked while an RVA is resolved, so resolving can look up others.
+    let image = &PeImage::new(rtti_image().leak(), ImageLayout::File).unwrap();
+    set_address_cache_path(&path);
+    let nested = cached_game_rva(
+        image,
+        "outer",
+        |_| false,
+        || cached_game_rva(image, "inner", |_| false, || Ok(0x1000)).map(|rva| rva + 4),
+    );
+    assert_eq!(nested, Ok(0x1004));
+    let cache = AddressCache::load(&path, image);
+    assert_eq!(
+        (cache.get("outer"), cache.get("inner")),
+        (Some(0x1004), Some(0x1000))
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn symbols_are_validated() {
+    let file = rtti_image();
+    let image = PeImage::new(&file, ImageLayout::File).unwrap();
+    let validator = SymbolValidator::empty()
+        .function_with_prologue("function", "CC CC ?? CC")
+        .unwrap()
+        .function_with_prologue("wrong prologue", "48 89 5C 24")
+        .unwrap()
+        .function("outside text")
+        .vtable("vtable", "CS::CSEzTask")
+        .vtable("wrong vtable", "FD4::FD4TaskBase")
+        .function("missing");
+    let rvas = [
+        ("function", 0x1000),
+        ("wrong prologue", 0x1004),
+        ("outside text", 0x2148),
+        ("vtable", 0x2148),
+        ("wrong vtable", 0x2148),
+    ];
+    let report = validator.validate_image(&image, |name| {
+        rvas.iter()
+            .find(|&&(symbol, _)| symbol == name)
+            .map(|&(_, rva)| rva)
+            .ok_or_else(|| LiberError::MissingSymbol(name.to_string()))
+    });
+    let results: Vec<_> = report
+        .symbols
+        .iter()
+        .map(|symbol| (symbol.name.as_str(), symbol.result.clone()))
+        .collect();
+    assert_eq!(
+        results,
+        [
+            ("function", Ok(0x1000)),
+            (
+                "wrong prologue",
+                Err(LiberError::PrologueMismatch("wrong prologue".into()))
+            ),
+            (
+                "outside text",
+                Err(LiberError::SymbolOutsideText("outside text".into()))
+            ),
+            ("vtable", Ok(0x2148)),
+            (
+                "wrong vtable",
+                Err(LiberError::VTableMismatch(
+                    "wrong vtable".into(),
+                    "FD4::FD4TaskBase".into()
+                ))
+            ),
+            ("missing", Err(LiberError::MissingSymbol("missing".into()))),
+        ]
+    );
+    assert!(!report.is_ok());
+    assert_eq!(report.failures().count(), 4);
+    assert!(report
+        .to_string()
+        .starts_with("ok   function at 0x1000\nFAIL wrong prologue: "));
+    assert_eq!(
+        report.into_result(),
+        Err(LiberError::InvalidSymbols(vec![
+            "wrong prologue".into(),
+            "outside text".into(),
+            "wrong vtable".into(),
+            "missing".into(),
+        ]))
+    );
+    assert_eq!(
+        SymbolValidator::new()
+            .symbols()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>(),
+        ["CS::CSEzTask::register_task", "CS::CSEzTask::free_task"]
+    );
+}
+
+#[test]
+fn branch_targets_are_decoded() {
+    let branches: [(&[u8], usize, usize); 7] = [
+        (&[0xE8, 0x10, 0x00, 0x00, 0x00], 5, 0x1015),
+        (&[0xE8, 0xFB, 0xFF, 0xFF, 0xFF], 5, 0x1000),
+        (&[0xE9, 0x00, 0x01, 0x00, 0x00], 5, 0x1105),
+        (&[0xEB, 0x02], 2, 0x1004),
+        (&[0x74, 0xFE], 2, 0x1000),
+        (&[0x0F, 0x84, 0x10, 0x00, 0x00, 0x00], 6, 0x1016),
+        (&[0xE2, 0x80], 2, 0xF82),
+    ];
+    for (code, len, target) in branches {
+        let instruction = decode(code, 0x1000).unwrap();
+        assert_eq!(instruction.len, len, "{code:02X?}");
+        assert_eq!(instruction.branch_target, Some(target), "{code:02X?}");
+        assert_eq!(instruction.rip_target, None, "{code:02X?}");
+    }
+
+    // Targets wrap around the end of the address space, like they do for the CPU.
+    let call = decode(&[0xE8, 0x00, 0x00, 0x00, 0x00], usize::MAX - 2).unwrap();
+    assert_eq!(call.branch_target, Some(2));
+    assert_eq!(call.next(), 2);
+    let load = decode(&[0x48, 0x8B, 0x05, 0x00, 0x00, 0x00, 0x00], usize::MAX - 2).unwrap();
+    assert_eq!(load.rip_target, Some(4));
+
+    // Prefixes do not make a branch longer than any instruction can be.
+    let mut padded = vec![0x66; 11];
+    padded.extend([0xE8, 0x00, 0x00, 0x00, 0x00]);
+    assert_eq!(
+        decode(&padded, 0x1000),
+        Err(LiberError::InvalidInstruction(0x1000))
+    );
+    assert_eq!(decode(&padded[1..], 0x1000).unwrap().len, 15);
+}
+
+#[test]
+fn rip_relative_operands_are_decoded() {
+    let address = 0x140001000;
+    let operands: [(&[u8], usize, Option<usize>); 8] = [
+        // mov rax, [rip + 0x12345678]
+        (
+            &[0x48, 0x8B, 0x05, 0x78, 0x56, 0x34, 0x12],
+            7,
+            Some(0x15234667F),
+        ),
+        // lea rcx, [rip - 0x10]
+        (
+            &[0x48, 0x8D, 0x0D, 0xF0, 0xFF, 0xFF, 0xFF],
+            7,
+            Some(0x140000FF7),
+        ),
+        // mov dword [rip + 0x10], 1
+        (
+            &[0xC7, 0x05, 0x10, 0, 0, 0, 1, 0, 0, 0],
+            10,
+            Some(0x14000101A),
+        ),
+        // mov word [rip + 0x10], 1
+        (
+            &[0x66, 0xC7, 0x05, 0x10, 0, 0, 0, 1, 0],
+            9,
+            Some(0x140001019),
+        ),
+        // cmp byte [rip + 0x10], 0
+        (&[0x80, 0x3D, 0x10, 0, 0, 0, 0], 7, Some(0x140001017)),
+        // test dword [rip + 0x10], 0x100 and not byte [rip + 0x10]
+        (
+            &[0xF7, 0x05, 0x10, 0, 0, 0, 0, 1, 0, 0],
+            10,
+            Some(0x14000101A),
+        ),
+        (&[0xF6, 0x15, 0x10, 0, 0, 0], 6, Some(0x140001016)),
+        // vmovups xmm0, [rip + 0x10]
+        (
+            &[0xC5, 0xF8, 0x10, 0x05, 0x10, 0, 0, 0],
+            8,
+            Some(0x140001018),
+        ),
+    ];
+    for (code, len, target) in operands {
+        let instruction = decode(code, address).unwrap();
+        assert_eq!(instruction.len, len, "{code:02X?}");
+        assert_eq!(instruction.rip_target, target, "{code:02X?}");
+        assert_eq!(instruction.branch_target, None, "{code:02X?}");
+    }
+}
+
+#[test]
+fn instruction_lengths_are_decoded() {
+    let instructions: [(&[u8], usize); 9] = [
+        // mov [rsp + 8], rbx
+        (&[0x48, 0x89, 0x5C, 0x24, 0x08], 5),
+        // sub rsp, 0x28
+        (&[0x48, 0x83, 0xEC, 0x28], 4),
+        // mov rax, imm64 and mov eax, imm32
+        (&[0x48, 0xB8, 1, 2, 3, 4, 5, 6, 7, 8], 10),
+        (&[0xB8, 1, 2, 3, 4], 5),
+        // nop dword [rax + rax]
+        (&[0x0F, 0x1F, 0x44, 0x00, 0x00], 5),
+        // mov eax, [rbp + 0x100]
+        (&[0x8B, 0x85, 0x00, 0x01, 0x00, 0x00], 6),
+        // ret and int3
+        (&[0xC3], 1),
+        (&[0xCC], 1),
+        // pshufd xmm0, xmm1, 0x1B
+        (&[0x66, 0x0F, 0x70, 0xC1, 0x1B], 5),
+    ];
+    for (code, len) in instructions {
+        let mut padded = code.to_vec();
+        padded.extend([0xCC; 4]);
+        assert_eq!(decode(&padded, 0).map(|i| i.len), Ok(len), "{code:02X?}");
+    }
+    assert_eq!(
+        decode(&[0x06], 0x1000),
+        Err(LiberError::InvalidInstruction(0x1000))
+    );
+    assert_eq!(
+        decode(&[0xE8, 0x00], 0x1000),
+        Err(LiberError::InvalidInstruction(0x1000))
+    );
+    assert_eq!(
+        decode(&[0x48, 0x8B, 0x05, 0x00], 0x1000),
+        Err(LiberError::InvalidInstruction(0x1000))
+    );
+}
+
+#[test]
+fn pointer_chains_are_parsed() {
+    let chain: PointerChain = "[[base+0x3D5DF38]+8]+68".parse().unwrap();
+    assert_eq!(chain.base(), &ChainBase::Module(None));
+    assert_eq!(
+        chain.steps(),
+        [
+            ChainStep::Offset(0x3D5DF38),
+            ChainStep::Deref,
+            ChainStep::Offset(0x8),
+            ChainStep::Deref,
+            ChainStep::Offset(0x68),
+        ]
+    );
+    assert_eq!(chain, PointerChain::game().offsets(&[0x3D5DF38, 0x8, 0x68]));
+    assert_eq!(chain.to_string(), "[[base+3D5DF38]+8]+68");
+
+    let chain = PointerChain::parse(" [ [eldenring.exe + 10] - 8 ] ").unwrap();
+    assert_eq!(
+        chain.base(),
+        &ChainBase::Module(Some("eldenring.exe".into()))
+    );
+    assert_eq!(chain.to_string(), "[[eldenring.exe+10]-8]");
+    let chain = PointerChain::parse("[7FF600001000]").unwrap();
+    assert_eq!(chain.base(), &ChainBase::Address(0x7FF600001000));
+    assert_eq!(PointerChain::parse(&chain.to_string()), Ok(chain));
+    // Hexadecimal bases are addresses, modules are told apart by their extension.
+    let chain = PointerChain::parse("cafe+8").unwrap();
+    assert_eq!(chain.base(), &ChainBase::Address(0xCAFE));
+    let chain = PointerChain::parse("cafe.dll+8").unwrap();
+    assert_eq!(chain.base(), &ChainBase::Module(Some("cafe.dll".into())));
+
+    for invalid in ["", "[base+8", "base+8]", "base+", "base+xyz", "[]+8"] {
+        assert_eq!(
+            PointerChain::parse(invalid),
+            Err(LiberError::InvalidPointerChain(invalid.into()))
+        );
+    }
+}
+
+#[test]
+fn pointer_chains_are_resolved_in_memory() {
+    // A global at 0x1000 points to an object at 0x2000, which points to another at 0x3000.
+    let memory = FakeMemory::new()
+        .with_region(0x1000, 0x2000usize.to_le_bytes())
+        .with_region(0x2000, [0x3000usize.to_le_bytes(), [0; 8]].concat())
+        .with_region(0x3000, [0u8; 0x80]);
+    let chain = PointerChain::new(ChainBase::Address(0x1000)).offsets(&[0, 0, 0x68]);
+    assert_eq!(chain.resolve_in(&memory), Ok(0x3068));
+    chain.write_in(&memory, 0xDEADBEEFu32).unwrap();
+    assert_eq!(chain.read_in::<u32>(&memory), Ok(0xDEADBEEF));
+    assert_eq!(chain.read_in::<[u16; 2]>(&memory), Ok([0xBEEF, 0xDEAD]));
+
+    let null = PointerChain::new(ChainBase::Address(0x1000)).offsets(&[0, 8, 0]);
+    assert_eq!(
+        null.resolve_in(&memory),
+        Err(LiberError::NullPointerInChain("[[1000+0]+8]+0".into(), 2))
+    );
+    let unreadable = PointerChain::new(ChainBase::Address(0x1000)).offsets(&[0x10, 0]);
+    assert_eq!(
+        unreadable.resolve_in(&memory),
+        Err(LiberError::UnreadableMemory(0x1010))
+    );
+    let past_end = PointerChain::new(ChainBase::Address(0x3000)).offset(0x7E);
+    assert_eq!(
+        past_end.read_in::<u32>(&memory),
+        Err(LiberError::UnreadableMemory(0x307E))
+    );
+    assert_eq!(
+        past_end.write_in(&memory, 0u32),
+        Err(LiberError::UnwritableMemory(0x307E))
+    );
+
+    // Chains are resolved again on every access, unless they are cached.
+    let cached = chain.clone().cached();
+    assert_eq!(cached.resolve_in(&memory), Ok(0x3068));
+    memory.insert(0x1000, 0x2008usize.to_le_bytes());
+    assert_eq!(
+        chain.resolve_in(&memory),
+        Err(LiberError::NullPointerInChain(chain.to_string(), 2))
+    );
+    assert_eq!(cached.resolve_in(&memory), Ok(0x3068));
+    cached.invalidate();
+    assert!(cached.resolve_in(&memory).is_err());
+}
@@ -0,0 +1,278 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+use crate::from::CS::{CSEzTaskFreeTask, CSEzTaskRegisterTask};
+use crate::{
+    detect_game_version, game_version, is_vtable_of, symbol_database, FunctionSymbol, LiberError,
+    Pattern, PeImage,
+};
+
+/// What a symbol has to be, to pass validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A function in `.text`, which starts with `prologue` if it is set, or else with the prologue the symbol
+    /// database has for it, if any. If the exception directory has an entry for the function, the symbol has
+    /// to be at its start.
+    Function { prologue: Option<Pattern> },
+    /// The vtable of `class`, checked through its RTTI. See `is_vtable_of`.
+    VTable { class: String },
+}
+
+/// The symbols the crate and a mod use, to check them all at startup, before anything is called in the game.
+/// The symbols are resolved through the symbol database, like `resolve_symbol` does, unless they are added
+/// with `resolved`.
+///
+/// ```ignore
+/// let report = SymbolValidator::new()
+///     .function_with_prologue("MyMod::update", "48 89 5C 24 ?? 57 48 83 EC 20")?
+///     .vtable("CS::CSEzTaskProxy::vftable", "CS::CSEzTaskProxy")
+///     .resolved("MyMod::draw", SymbolKind::Function { prologue: None }, || {
+///         Pattern::new("48 8B C4 55 41 56")?.find_in_game(".text")
+///     })
+///     .validate();
+/// report.into_result()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct SymbolValidator {
+    symbols: Vec<(String, SymbolKind, Option<Resolver>)>,
+}
+
+/// Finds the address of a symbol in the running game, instead of the symbol database.
+#[derive(Clone)]
+struct Resolver(Arc<dyn Fn() -> Result<usize, LiberError> + Send + Sync>);
+
+impl Debug for Resolver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Resolver")
+    }
+}
+
+impl SymbolValidator {
+    /// A validator for the symbols the crate itself calls, like `CS::CSEzTask::register_task`. Their prologues
+    /// come from the symbol database, as they differ between game versions.
+    pub fn new() -> Self {
+        Self::empty()
+            .function(CSEzTaskRegisterTask::NAME)
+            .function(CSEzTaskFreeTask::NAME)
+    }
+    /// A validator without any symbols.
+    pub const fn empty() -> Self {
+        Self {
+            symbols: Vec::new(),
+        }
+    }
+    /// Check `name` as a symbol of kind `kind`.
+    pub fn symbol(mut self, name: impl Into<String>, kind: SymbolKind) -> Self {
+        self.symbols.push((name.into(), kind, None));
+        self
+    }
+    /// Check `name` as a symbol of kind `kind`, at the address `resolve` finds in the running game, for
+    /// symbols that are not in the symbol database, like the ones `liber_fn!` finds with `pattern` or
+    /// `resolve`, or vtables found with `find_vtable`. `validate_image` resolves them with its `resolve`.
+    pub fn resolved(
+        mut self,
+        name: impl Into<String>,
+        kind: SymbolKind,
+        resolve: impl Fn() -> Result<usize, LiberError> + Send + Sync + 'static,
+    ) -> Self {
+        self.symbols
+            .push((name.into(), kind, Some(Resolver(Arc::new(resolve)))));
+        self
+    }
+    /// Check that `name` is a function in `.text`.
+    pub fn function(self, name: impl Into<String>) -> Self {
+        self.symbol(name, SymbolKind::Function { prologue: None })
+    }
+    /// Check that `name` is a function in `.text`, which starts with the bytes of the IDA-style `prologue`.
+    pub fn function_with_prologue(
+        self,
+        name: impl Into<String>,
+        prologue: &str,
+    ) -> Result<Self, LiberError> {
+        let prologue = Some(Pattern::new(prologue)?);
+        Ok(self.symbol(name, SymbolKind::Function { prologue }))
+    }
+    /// Check that `name` is the vtable of `class`.
+    pub fn vtable(self, name: impl Into<String>, class: impl Into<String>) -> Self {
+        let class = class.into();
+        self.symbol(name, SymbolKind::VTable { class })
+    }
+    /// The symbols that are checked.
+    pub fn symbols(&self) -> impl Iterator<Item = (&str, &SymbolKind)> {
+        self.symbols
+            .iter()
+            .map(|(name, kind, _)| (name.as_str(), kind))
+    }
+    /// Check every symbol in the running game, with the RVAs for the game version, which is detected first if
+    /// it was not yet.
+    pub fn validate(&self) -> ValidationReport {
+        let image = PeImage::game();
+        let version = game_version().map(Ok).unwrap_or_else(detect_game_version);
+        let symbols = self.symbols.iter().map(|(name, kind, resolver)| {
+            let database = version
+                .as_ref()
+                .ok()
+                .and_then(|version| symbol_database().version(version).ok());
+            let result = image.clone().and_then(|image| {
+                let rva = match resolver {
+                    Some(Resolver(resolve)) => game_rva(&image, resolve()?)?,
+                    None => {
+                        let rva =
+                            symbol_database().rva(version.as_ref().map_err(Clone::clone)?, name)?;
+                        u32::try_from(rva).map_err(|_| LiberError::MissingSymbol(name.clone()))?
+                    }
+                };
+                let prologue = database.and_then(|symbols| symbols.prologue(name));
+                check(&image, name, kind, rva, prologue.as_ref()).map(|()| rva)
+            });
+            SymbolReport::new(name, result)
+        });
+        ValidationReport {
+            symbols: symbols.collect(),
+        }
+    }
+    /// Check every symbol in `image`, i.e. a game executable on disk, with the RVAs from `resolve`.
+    pub fn validate_image(
+        &self,
+        image: &PeImage,
+        resolve: impl Fn(&str) -> Result<u32, LiberError>,
+    ) -> ValidationReport {
+        let symbols = self.symbols.iter().map(|(name, kind, _)| {
+            let result =
+                resolve(name).and_then(|rva| check(image, name, kind, rva, None).map(|()| rva));
+            SymbolReport::new(name, result)
+        });
+        ValidationReport {
+            symbols: symbols.collect(),
+        }
+    }
+}
+
+impl Default for SymbolValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The RVA of `address` in the game executable in `image`.
+fn game_rva(image: &PeImage, address: usize) -> Result<u32, LiberError> {
+    address
+        .checked_sub(image.data().as_ptr() as usize)
+        .filter(|&rva| rva < image.size_of_image() as usize)
+        .map(|rva| rva as u32)
+        .ok_or_else(|| {
+            LiberError::InvalidImage(format!("{address:#x} is not in the game executable"))
+        })
+}
+
+/// Check that the symbol `name` at `rva` in `image` is a `kind`. Functions without a prologue of their own are
+/// checked against `database_prologue`.
+fn check(
+    image: &PeImage,
+    name: &str,
+    kind: &SymbolKind,
+    rva: u32,
+    database_prologue: Option<&Pattern>,
+) -> Result<(), LiberError> {
+    match kind {
+        SymbolKind::Function { prologue } => {
+            let text = image.section(".text")?;
+            let end = text
+                .virtual_address
+                .checked_add(text.virtual_size)
+                .ok_or_else(|| LiberError::InvalidImage(".text overflows".into()))?;
+            if !(text.virtual_address..end).contains(&rva) {
+                return Err(LiberError::SymbolOutsideText(name.to_string()));
+            }
+            // Leaf functions have no entry, so only symbols inside a function that has one are caught.
+            if image
+                .function_containing(rva)?
+                .is_some_and(|function| function.begin != rva)
+            {
+                return Err(LiberError::NotFunctionStart(name.to_string()));
+            }
+            match prologue.as_ref().or(database_prologue) {
+                Some(prologue) => image
+                    .read(rva, prologue.len())
+                    .filter(|code| prologue.matches_at(code, 0))
+                    .map(|_| ())
+                    .ok_or_else(|| LiberError::PrologueMismatch(name.to_string())),
+                None => Ok(()),
+            }
+        }
+        SymbolKind::VTable { class } => {
+            if is_vtable_of(image, rva, class) {
+                Ok(())
+            } else {
+                Err(LiberError::VTableMismatch(name.to_string(), class.clone()))
+            }
+        }
+    }
+}
+
+/// The result of validating one symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolReport {
+    /// The name of the symbol.
+    pub name: String,
+    /// The RVA of the symbol if it passed, or why it failed.
+    pub result: Result<u32, LiberError>,
+}
+
+impl SymbolReport {
+    fn new(name: &str, result: Result<u32, LiberError>) -> Self {
+        Self {
+            name: name.to_string(),
+            result,
+        }
+    }
+    /// Returns `true` if the symbol passed every check.
+    pub fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl Display for SymbolReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.result {
+            Ok(rva) => write!(f, "ok   {} at {rva:#x}", self.name),
+            Err(error) => write!(f, "FAIL {}: {error}", self.name),
+        }
+    }
+}
+
+/// The results of `SymbolValidator::validate`, one per symbol, in the order they were added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub symbols: Vec<SymbolReport>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if every symbol passed.
+    pub fn is_ok(&self) -> bool {
+        self.symbols.iter().all(SymbolReport::passed)
+    }
+    /// The symbols that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &SymbolReport> {
+        self.symbols.iter().filter(|symbol| !symbol.passed())
+    }
+    /// Fails with `LiberError::InvalidSymbols` with the names of the symbols that failed, so the mod can
+    /// refuse to run with `?`.
+    pub fn into_result(self) -> Result<(), LiberError> {
+        let failures: Vec<String> = self.failures().map(|symbol| symbol.name.clone()).collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(LiberError::InvalidSymbols(failures))
+        }
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for symbol in &self.symbols {
+            writeln!(f, "{symbol}")?;
+        }
+        Ok(())
+    }
+}
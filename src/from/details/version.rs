@@ -0,0 +1,37 @@
+use std::fmt::{Display, Formatter};
+
+use crate::from::details::pe::{FileVersion, PeImage};
+use crate::LiberError;
+
+/// What identifies a build of the game, to select the symbols of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameBuild {
+    /// The link timestamp in the PE header of the executable, which differs for every build.
+    pub timestamp: u32,
+    /// The file version in the version resource of the executable.
+    pub file_version: Option<FileVersion>,
+}
+
+impl GameBuild {
+    /// The build of the executable in `image`.
+    pub fn of(image: &PeImage) -> Result<Self, LiberError> {
+        Ok(Self {
+            timestamp: image.timestamp(),
+            file_version: image.file_version()?,
+        })
+    }
+    /// The build of the running game.
+    pub fn detect() -> Result<Self, LiberError> {
+        Self::of(&PeImage::game()?)
+    }
+}
+
+impl Display for GameBuild {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timestamp {:#x}", self.timestamp)?;
+        match self.file_version {
+            Some(version) => write!(f, ", file version {version}"),
+            None => write!(f, ", no file version"),
+        }
+    }
+}
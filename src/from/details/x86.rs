@@ -0,0 +1,282 @@
+use crate::LiberError;
+
+/// The longest x86-64 instruction.
+pub const MAX_INSTRUCTION_LEN: usize = 15;
+
+/// An x86-64 instruction, decoded as far as needed to find its length and the addresses it refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    /// The address the instruction is at.
+    pub address: usize,
+    /// The length of the instruction in bytes.
+    pub len: usize,
+    /// The target of a relative call, jump or loop, i.e. of `E8 rel32`.
+    pub branch_target: Option<usize>,
+    /// The address of a RIP-relative memory operand, i.e. of the global in `48 8B 05 disp32`.
+    pub rip_target: Option<usize>,
+}
+
+impl Instruction {
+    /// The address of the next instruction.
+    pub fn next(&self) -> usize {
+        self.address.wrapping_add(self.len)
+    }
+}
+
+/// The size of an immediate operand.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Immediate {
+    None,
+    Byte,
+    Word,
+    /// 32 bits, or 16 bits with an operand size prefix.
+    Full,
+    /// `imm16, imm8` of `enter`.
+    Enter,
+    /// 64 bits with `REX.W`, otherwise like `Full`, for `mov r64, imm64`.
+    Wide,
+    /// A 64-bit address, or 32 bits with an address size prefix, for `mov al, moffs`.
+    Offset,
+    Rel8,
+    Rel32,
+}
+
+/// The operands of an opcode in the one byte map, or `None` for opcodes that are invalid in 64-bit mode.
+fn one_byte(opcode: u8) -> Option<(bool, Immediate)> {
+    use Immediate::*;
+    Some(match opcode {
+        0x06 | 0x07 | 0x0E | 0x16 | 0x17 | 0x1E | 0x1F | 0x27 | 0x2F | 0x37 | 0x3F | 0x60
+        | 0x61 | 0x82 | 0x9A | 0xD4 | 0xD5 | 0xD6 | 0xEA => return Option::None,
+        0x00..=0x3F if opcode & 7 < 4 => (true, None),
+        0x00..=0x3F if opcode & 7 == 4 => (false, Byte),
+        0x00..=0x3F if opcode & 7 == 5 => (false, Full),
+        0x63 | 0x84..=0x8F | 0xD0..=0xD3 | 0xD8..=0xDF | 0xFE | 0xFF => (true, None),
+        0x68 => (false, Full),
+        0x69 | 0x81 | 0xC7 => (true, Full),
+        0x6A | 0xA8 | 0xB0..=0xB7 | 0xCD | 0xE4..=0xE7 => (false, Byte),
+        0x6B | 0x80 | 0x83 | 0xC0 | 0xC1 | 0xC6 => (true, Byte),
+        0x70..=0x7F | 0xE0..=0xE3 | 0xEB => (false, Rel8),
+        0xA0..=0xA3 => (false, Offset),
+        0xA9 => (false, Full),
+        0xB8..=0xBF => (false, Wide),
+        0xC2 | 0xCA => (false, Word),
+        0xC8 => (false, Enter),
+        0xE8 | 0xE9 => (false, Rel32),
+        0xF6 | 0xF7 => (true, None),
+        _ => (false, None),
+    })
+}
+
+/// The operands of an opcode in the `0F` map.
+fn two_byte(opcode: u8) -> (bool, Immediate) {
+    use Immediate::*;
+    match opcode {
+        0x05..=0x09
+        | 0x0B
+        | 0x0E
+        | 0x30..=0x37
+        | 0x77
+        | 0xA0..=0xA2
+        | 0xA8
+        | 0xA9
+        | 0xC8..=0xCF => (false, None),
+        0x80..=0x8F => (false, Rel32),
+        0x70..=0x73 | 0xA4 | 0xAC | 0xBA | 0xC2 | 0xC4..=0xC6 => (true, Byte),
+        _ => (true, None),
+    }
+}
+
+/// Decode the instruction at the start of `code`, which is at `address`.
+pub fn decode(code: &[u8], address: usize) -> Result<Instruction, LiberError> {
+    let invalid = || LiberError::InvalidInstruction(address);
+    let byte = |at: usize| code.get(at).copied().ok_or_else(invalid);
+
+    let mut at = 0;
+    let mut operand_size = false;
+    let mut address_size = false;
+    loop {
+        match byte(at)? {
+            0x66 => operand_size = true,
+            0x67 => address_size = true,
+            0x26 | 0x2E | 0x36 | 0x3E | 0x64 | 0x65 | 0xF0 | 0xF2 | 0xF3 => {}
+            _ => break,
+        }
+        at += 1;
+    }
+    let mut rex_w = false;
+    if let 0x40..=0x4F = byte(at)? {
+        rex_w = byte(at)? & 0x08 != 0;
+        at += 1;
+    }
+
+    // `test` in the group of `F6` and `F7` in the one byte map has an immediate, the other instructions do not.
+    let mut test_group = false;
+    let (modrm, mut immediate) = match byte(at)? {
+        // VEX and EVEX prefixes, which select the map of the opcode after them.
+        prefix @ (0xC4 | 0xC5 | 0x62) => {
+            let map = match prefix {
+                0xC5 => 1,
+                0xC4 => byte(at + 1)? & 0x1F,
+                _ => byte(at + 1)? & 0x07,
+            };
+            at += match prefix {
+                0xC5 => 2,
+                0xC4 => 3,
+                _ => 4,
+            };
+            let opcode = byte(at)?;
+            at += 1;
+            match map {
+                1 => two_byte(opcode),
+                3 => (true, Immediate::Byte),
+                2 | 5 | 6 => (true, Immediate::None),
+                _ => return Err(invalid()),
+            }
+        }
+        0x0F => {
+            at += 1;
+            let opcode = byte(at)?;
+            at += 1;
+            match opcode {
+                0x38 => {
+                    at += 1;
+                    (true, Immediate::None)
+                }
+                0x3A => {
+                    at += 1;
+                    (true, Immediate::Byte)
+                }
+                _ => {
+                    let (modrm, immediate) = two_byte(opcode);
+                    if immediate == Immediate::Rel32 {
+                        return branch(code, address, at, 4);
+                    }
+                    (modrm, immediate)
+                }
+            }
+        }
+        opcode => {
+            at += 1;
+            test_group = matches!(opcode, 0xF6 | 0xF7);
+            one_byte(opcode).ok_or_else(invalid)?
+        }
+    };
+
+    let mut rip_displacement = None;
+    if modrm {
+        let modrm = byte(at)?;
+        at += 1;
+        let (mode, reg, rm) = (modrm >> 6, (modrm >> 3) & 7, modrm & 7);
+        if test_group && reg < 2 {
+            immediate = if code[at - 2] == 0xF6 {
+                Immediate::Byte
+            } else {
+                Immediate::Full
+            };
+        }
+        if mode != 3 {
+            if rm == 4 {
+                let base = byte(at)? & 7;
+                at += 1;
+                if mode == 0 && base == 5 {
+                    at += 4;
+                }
+            } else if mode == 0 && rm == 5 {
+                rip_displacement = Some(at);
+                at += 4;
+            }
+            at += match mode {
+                1 => 1,
+                2 => 4,
+                _ => 0,
+            };
+        }
+    }
+
+    let immediate = match immediate {
+        Immediate::None => 0,
+        Immediate::Byte => 1,
+        Immediate::Word => 2,
+        Immediate::Full if operand_size => 2,
+        Immediate::Full => 4,
+        Immediate::Enter => 3,
+        Immediate::Wide if rex_w => 8,
+        Immediate::Wide if operand_size => 2,
+        Immediate::Wide => 4,
+        Immediate::Offset if address_size => 4,
+        Immediate::Offset => 8,
+        Immediate::Rel8 => return branch(code, address, at, 1),
+        Immediate::Rel32 => return branch(code, address, at, 4),
+    };
+    let len = at + immediate;
+    if len > MAX_INSTRUCTION_LEN || len > code.len() {
+        return Err(invalid());
+    }
+    let rip_target = rip_displacement.map(|displacement| {
+        let displacement =
+            i32::from_le_bytes(code[displacement..displacement + 4].try_into().unwrap());
+        address
+            .wrapping_add(len)
+            .wrapping_add_signed(displacement as isize)
+    });
+    Ok(Instruction {
+        address,
+        len,
+        branch_target: None,
+        rip_target,
+    })
+}
+
+/// A relative branch with a displacement of `size` bytes at `at`, which ends the instruction.
+fn branch(code: &[u8], address: usize, at: usize, size: usize) -> Result<Instruction, LiberError> {
+    let displacement = code
+        .get(at..at + size)
+        .ok_or(LiberError::InvalidInstruction(address))?;
+    let displacement = match size {
+        1 => displacement[0] as i8 as isize,
+        _ => i32::from_le_bytes(displacement.try_into().unwrap()) as isize,
+    };
+    let len = at + size;
+    if len > MAX_INSTRUCTION_LEN {
+        return Err(LiberError::InvalidInstruction(address));
+    }
+    Ok(Instruction {
+        address,
+        len,
+        branch_target: Some(address.wrapping_add(len).wrapping_add_signed(displacement)),
+        rip_target: None,
+    })
+}
+
+/// Decode the instruction at `address` in memory.
+///
+/// # Safety
+/// `address` has to point to an instruction in mapped memory, i.e. in the game executable, followed by at
+/// least `MAX_INSTRUCTION_LEN` readable bytes.
+pub unsafe fn decode_at(address: usize) -> Result<Instruction, LiberError> {
+    decode(
+        std::slice::from_raw_parts(address as *const u8, MAX_INSTRUCTION_LEN),
+        address,
+    )
+}
+
+/// The target of the relative call or jump at `address`, i.e. of an `E8 rel32` a pattern was found at.
+///
+/// # Safety
+/// See `decode_at`.
+pub unsafe fn branch_target_at(address: usize) -> Result<usize, LiberError> {
+    decode_at(address)?
+        .branch_target
+        .ok_or(LiberError::InvalidInstruction(address))
+}
+
+/// The address of the RIP-relative memory operand of the instruction at `address`, i.e. of the global loaded
+/// by a `48 8B 05 disp32` a pattern was found at.
+///
+/// # Safety
+/// See `decode_at`.
+pub unsafe fn rip_target_at(address: usize) -> Result<usize, LiberError> {
+    decode_at(address)?
+        .rip_target
+        .ok_or(LiberError::InvalidInstruction(address))
+}
@@ -1,8 +1,11 @@
 use cstr::cstr;
-use widestring::widecstr;
+use widestring::u16cstr;
 
 use crate::from::DLRF::DLRuntimeClassType;
-use crate::{CppClass, DestructorFn, VTable};
+use crate::{
+    forget, guard, guard_or_abort, scalar_deleting_destructor, CppClass, DestructorFn, Inherits,
+    RuntimeType, VTable, VTableLayout,
+};
 
 pub type GetRuntimeClassFn<C> =
     extern "C" fn(&CppClass<C>) -> &'static crate::from::DLRF::DLRuntimeClass;
@@ -11,7 +14,7 @@ pub type FD4ComponentBase = CppClass<FD4ComponentBaseType>;
 const _: () = assert!(std::mem::size_of::<CppClass<FD4ComponentBaseType>>() == 0x8);
 
 #[repr(C)]
-pub struct FD4ComponentBaseVTable<C: VTable> {
+pub struct FD4ComponentBaseVTable<C: VTableLayout> {
     /// Get the runtime class object
     ///
     /// * `return`: `DLRF::DLRuntimeClass*` pointer to the runtime class
@@ -20,18 +23,27 @@ pub struct FD4ComponentBaseVTable<C: VTable> {
 }
 const _: () = assert!(std::mem::size_of::<FD4ComponentBaseVTable<FD4ComponentBaseType>>() == 0x10);
 
-impl<C: VTable> FD4ComponentBaseVTable<C>
+impl<C: VTableLayout> FD4ComponentBaseVTable<C>
 where
     CppClass<C>: FD4ComponentBaseTrait,
 {
     pub const fn new() -> Self {
         Self {
-            get_runtime_class: <CppClass<C> as DLRuntimeClassTrait>::get_runtime_class,
-            destructor: <CppClass<C> as FD4ComponentBaseTrait>::destructor,
+            get_runtime_class: get_runtime_class_shim::<CppClass<C>>,
+            destructor: component_destructor::<CppClass<C>>,
         }
     }
 }
 
+impl<C: VTableLayout> Default for FD4ComponentBaseVTable<C>
+where
+    CppClass<C>: FD4ComponentBaseTrait,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Reflection implementation for a given class.
 ///
 /// A class that automatically instantiates reflective DLRF::DLRuntimeClass
@@ -42,29 +54,123 @@ where
 pub struct FD4ComponentBaseType;
 const _: () = assert!(std::mem::size_of::<FD4ComponentBaseType>() == 0x0);
 
-impl VTable for FD4ComponentBaseType {
+impl VTableLayout for FD4ComponentBaseType {
     type Table = FD4ComponentBaseVTable<FD4ComponentBaseType>;
+}
+
+impl VTable for FD4ComponentBaseType {
     const TABLE: &'static Self::Table = &FD4ComponentBaseVTable {
-        get_runtime_class: FD4ComponentBase::get_runtime_class,
-        destructor: FD4ComponentBase::destructor,
+        get_runtime_class: get_runtime_class_shim::<FD4ComponentBase>,
+        destructor: component_destructor::<FD4ComponentBase>,
     };
 }
 
+impl<C: VTableLayout> AsRef<FD4ComponentBaseVTable<C>> for FD4ComponentBaseVTable<C> {
+    fn as_ref(&self) -> &FD4ComponentBaseVTable<C> {
+        self
+    }
+}
+
+impl RuntimeType for FD4ComponentBaseType {
+    type Root = FD4ComponentBaseType;
+    fn is_instance(root: &FD4ComponentBase) -> bool {
+        has_runtime_class(root, FD4ComponentBase::runtime_class())
+    }
+}
+
+/// Checks if the dynamic type of `object` is the class described by `class`, by comparing it with the
+/// `DLRuntimeClass` returned by the vtable of the object. See `DLRuntimeClass::is_same_class`.
+pub fn has_runtime_class<C>(object: &CppClass<C>, class: &crate::from::DLRF::DLRuntimeClass) -> bool
+where
+    C: Inherits<FD4ComponentBaseType>,
+{
+    object
+        .upcast::<FD4ComponentBaseType>()
+        .virtual_get_runtime_class()
+        .is_same_class(class)
+}
+
 pub trait DLRuntimeClassTrait {
-    extern "C" fn get_runtime_class(&self) -> &'static crate::from::DLRF::DLRuntimeClass;
+    /// The runtime class of the class. Runtime classes are compared by address, so it has to be a `static` of
+    /// this class alone.
+    fn runtime_class() -> &'static crate::from::DLRF::DLRuntimeClass
+    where
+        Self: Sized;
+    fn get_runtime_class(&self) -> &'static crate::from::DLRF::DLRuntimeClass
+    where
+        Self: Sized,
+    {
+        Self::runtime_class()
+    }
 }
 impl DLRuntimeClassTrait for FD4ComponentBase {
-    extern "C" fn get_runtime_class(&self) -> &'static crate::from::DLRF::DLRuntimeClass {
+    fn runtime_class() -> &'static crate::from::DLRF::DLRuntimeClass {
         static DL_RUNTIME_CLASS: crate::from::DLRF::DLRuntimeClass =
-            crate::from::DLRF::DLRuntimeClass::from_data(DLRuntimeClassType::new(
-                cstr!("FD4ComponentBase"),
-                widecstr!("FD4ComponentBase"),
-            ));
+            crate::from::DLRF::DLRuntimeClass::from_data(
+                DLRuntimeClassType::new::<FD4ComponentBase>(
+                    cstr!("FD4ComponentBase"),
+                    u16cstr!("FD4ComponentBase"),
+                ),
+            );
         &DL_RUNTIME_CLASS
     }
 }
 
+/// The `get_runtime_class` in the vtable, which catches panics in `DLRuntimeClassTrait::get_runtime_class`.
+pub extern "C" fn get_runtime_class_shim<T: DLRuntimeClassTrait>(
+    this: &T,
+) -> &'static crate::from::DLRF::DLRuntimeClass {
+    guard_or_abort("FD4ComponentBase::get_runtime_class", this, || {
+        this.get_runtime_class()
+    })
+}
+
 pub trait FD4ComponentBaseTrait: DLRuntimeClassTrait {
-    extern "C" fn destructor(&self) {}
+    /// The body of the destructor. The Rust drop glue of the object runs after it.
+    fn destructor(&self) {}
 }
 impl FD4ComponentBaseTrait for FD4ComponentBase {}
+
+/// The scalar deleting destructor in the vtable of every `FD4ComponentBase` created in Rust.
+///
+/// Calls `FD4ComponentBaseTrait::destructor` and drops the object. If the game sets the delete flag, the memory
+/// of the object is freed with the allocator it was created with. A panic leaks the object.
+///
+/// # Safety
+/// See `scalar_deleting_destructor`.
+pub unsafe extern "C" fn component_destructor<T: FD4ComponentBaseTrait>(
+    this: *mut T,
+    flags: u32,
+) -> *mut T {
+    guard("FD4ComponentBase::destructor", this, || {
+        scalar_deleting_destructor(this, flags, |this| this.destructor())
+    });
+    forget(this);
+    this
+}
+
+/// Virtual calls to the `FD4ComponentBase` methods of an object.
+///
+/// Unlike calling `FD4ComponentBaseTrait` methods, these calls go through the vtable the object actually points
+/// to, so overrides installed by a subclass or by the game are honored.
+pub trait FD4ComponentBaseVirtual {
+    fn virtual_get_runtime_class(&self) -> &'static crate::from::DLRF::DLRuntimeClass;
+    /// Destroy the object through its scalar deleting destructor.
+    ///
+    /// # Safety
+    /// `this` must point to a live object that is not used after this call. With `DELETE_FLAG` set, it must have
+    /// been allocated the way its destructor expects.
+    unsafe fn virtual_destructor(this: *mut Self, flags: u32);
+}
+
+impl<C: VTableLayout> FD4ComponentBaseVirtual for CppClass<C>
+where
+    C::Table: AsRef<FD4ComponentBaseVTable<C>>,
+{
+    fn virtual_get_runtime_class(&self) -> &'static crate::from::DLRF::DLRuntimeClass {
+        (self.vtable.as_ref().get_runtime_class)(self)
+    }
+    unsafe fn virtual_destructor(this: *mut Self, flags: u32) {
+        ((*this).vtable.as_ref().destructor)(this, flags);
+    }
+}
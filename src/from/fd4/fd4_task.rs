@@ -2,15 +2,16 @@ use std::ffi::c_void;
 use std::ops::Deref;
 
 use cstr::cstr;
-use widestring::widecstr;
+use widestring::u16cstr;
 
 use crate::from::CS;
 use crate::from::DLRF::DLRuntimeClassType;
-use crate::from::FD4::time::FD4Time;
+use crate::from::FD4::time::{FD4Time, FD4TimeType};
 use crate::from::FD4::{
-    DLRuntimeClassTrait, FD4ComponentBaseTrait, FD4ComponentBaseType, FD4ComponentBaseVTable,
+    has_runtime_class, DLRuntimeClassTrait, FD4ComponentBaseTrait, FD4ComponentBaseType,
+    FD4ComponentBaseVTable, FD4ComponentBaseVirtual,
 };
-use crate::{CppClass, VTable};
+use crate::{guard_task, CppClass, CppLayout, Inherits, RuntimeType, VTable, VTableLayout};
 
 pub type ExecuteFn<C> = extern "C" fn(_this: &CppClass<C>, data: &FD4TaskData);
 
@@ -18,37 +19,58 @@ pub type FD4TaskBase = CppClass<FD4TaskBaseType>;
 const _: () = assert!(std::mem::size_of::<CppClass<FD4TaskBaseType>>() == 0x10);
 
 #[repr(C)]
-pub struct FD4TaskBaseVTable<C: VTable> {
+pub struct FD4TaskBaseVTable<C: VTableLayout> {
     fd4component_base_vtable: FD4ComponentBaseVTable<C>,
     execute: ExecuteFn<C>,
 }
 const _: () = assert!(std::mem::size_of::<FD4TaskBaseVTable<FD4TaskBaseType>>() == 0x18);
-impl<C: VTable> FD4TaskBaseVTable<C>
+impl<C: VTableLayout> FD4TaskBaseVTable<C>
 where
     CppClass<C>: FD4TaskBaseTrait,
 {
     pub const fn new() -> Self {
         Self {
             fd4component_base_vtable: FD4ComponentBaseVTable::new(),
-            execute: <CppClass<C> as FD4TaskBaseTrait>::execute,
+            execute: execute_shim::<CppClass<C>>,
         }
     }
 }
 
+impl<C: VTableLayout> Default for FD4TaskBaseVTable<C>
+where
+    CppClass<C>: FD4TaskBaseTrait,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FD4ComponentBaseTrait for FD4TaskBase {}
 
 impl DLRuntimeClassTrait for FD4TaskBase {
-    extern "C" fn get_runtime_class(&self) -> &'static crate::from::DLRF::DLRuntimeClass {
+    fn runtime_class() -> &'static crate::from::DLRF::DLRuntimeClass {
         static DL_RUNTIME_CLASS: crate::from::DLRF::DLRuntimeClass =
-            crate::from::DLRF::DLRuntimeClass::from_data(DLRuntimeClassType::new(
+            crate::from::DLRF::DLRuntimeClass::from_data(DLRuntimeClassType::new::<FD4TaskBase>(
                 cstr!("FD4TaskBase"),
-                widecstr!("FD4TaskBase"),
+                u16cstr!("FD4TaskBase"),
             ));
         &DL_RUNTIME_CLASS
     }
 }
 
-impl<C: VTable> Deref for FD4TaskBaseVTable<C> {
+impl<C: VTableLayout> AsRef<FD4ComponentBaseVTable<C>> for FD4TaskBaseVTable<C> {
+    fn as_ref(&self) -> &FD4ComponentBaseVTable<C> {
+        &self.fd4component_base_vtable
+    }
+}
+
+impl<C: VTableLayout> AsRef<FD4TaskBaseVTable<C>> for FD4TaskBaseVTable<C> {
+    fn as_ref(&self) -> &FD4TaskBaseVTable<C> {
+        self
+    }
+}
+
+impl<C: VTableLayout> Deref for FD4TaskBaseVTable<C> {
     type Target = FD4ComponentBaseVTable<C>;
 
     fn deref(&self) -> &Self::Target {
@@ -90,26 +112,74 @@ impl Deref for FD4TaskBaseType {
     }
 }
 
-impl VTable for FD4TaskBaseType {
+impl VTableLayout for FD4TaskBaseType {
     type Table = FD4TaskBaseVTable<FD4TaskBaseType>;
+}
+
+impl VTable for FD4TaskBaseType {
     const TABLE: &'static Self::Table = &FD4TaskBaseVTable::new();
 }
 
+unsafe impl Inherits<FD4ComponentBaseType> for FD4TaskBaseType {}
+
+impl RuntimeType for FD4TaskBaseType {
+    type Root = FD4ComponentBaseType;
+    fn is_instance(root: &CppClass<FD4ComponentBaseType>) -> bool {
+        has_runtime_class(root, FD4TaskBase::runtime_class())
+    }
+}
+
 pub trait FD4TaskBaseTrait: FD4ComponentBaseTrait {
-    extern "C" fn execute(&self, data: &FD4TaskData);
+    fn execute(&self, data: &FD4TaskData);
+}
+
+/// The `execute` in the vtable, which catches panics in `FD4TaskBaseTrait::execute`. It is skipped once the
+/// task is disabled by the panic handler.
+pub extern "C" fn execute_shim<T: FD4TaskBaseTrait>(this: &T, data: &FD4TaskData) {
+    guard_task("FD4TaskBase::execute", this, || this.execute(data))
 }
 
 impl FD4TaskBaseTrait for FD4TaskBase {
-    extern "C" fn execute(&self, data: &FD4TaskData) {
-        unimplemented!("{data:?}")
+    /// `FD4TaskBase` does nothing when executed, tasks override this.
+    fn execute(&self, _data: &FD4TaskData) {}
+}
+
+/// Virtual calls to the `FD4TaskBase` methods of an object.
+///
+/// Unlike calling `FD4TaskBaseTrait` methods, these calls go through the vtable the object actually points to,
+/// so overrides installed by a subclass or by the game are honored.
+pub trait FD4TaskBaseVirtual: FD4ComponentBaseVirtual {
+    fn virtual_execute(&self, data: &FD4TaskData);
+}
+
+impl<C: VTableLayout> FD4TaskBaseVirtual for CppClass<C>
+where
+    C::Table: AsRef<FD4TaskBaseVTable<C>> + AsRef<FD4ComponentBaseVTable<C>>,
+{
+    fn virtual_execute(&self, data: &FD4TaskData) {
+        (AsRef::<FD4TaskBaseVTable<C>>::as_ref(self.vtable).execute)(self, data)
     }
 }
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, CppLayout)]
+#[size(0x18)]
 pub struct FD4TaskData {
+    #[offset(0x0)]
     time: FD4Time,
+    #[offset(0x10)]
     task_group_id: CS::cstgi,
+    #[offset(0x14)]
     seed: i32,
 }
-const _: () = assert!(std::mem::size_of::<FD4TaskData>() == 0x18);
+
+impl FD4TaskData {
+    /// Task data like the game passes to tasks, i.e. for executing a task in tests.
+    pub fn new(delta_time: f32, task_group_id: CS::cstgi, seed: i32) -> Self {
+        Self {
+            time: FD4Time::from_data(FD4TimeType::new(delta_time)),
+            task_group_id,
+            seed,
+        }
+    }
+}
@@ -1,7 +1,10 @@
-use crate::{CppClass, DestructorFn, VTable};
+use crate::{
+    forget, guard, scalar_deleting_destructor, CppClass, DestructorFn, RuntimeType, VTable,
+    VTableLayout,
+};
 
 #[repr(C)]
-pub struct FD4TimeVTable<C: VTable> {
+pub struct FD4TimeVTable<C: VTableLayout> {
     destructor: DestructorFn<C>,
 }
 const _: () = assert!(std::mem::size_of::<FD4TimeVTable<FD4TimeType>>() == 0x8);
@@ -16,24 +19,89 @@ pub struct FD4TimeType {
 }
 const _: () = assert!(std::mem::size_of::<FD4TimeType>() == 0x4);
 
-impl VTable for FD4TimeType {
+impl FD4TimeType {
+    pub const fn new(time: f32) -> Self {
+        Self { time }
+    }
+}
+
+impl VTableLayout for FD4TimeType {
     type Table = FD4TimeVTable<FD4TimeType>;
-    const TABLE: &'static Self::Table = &FD4TimeVTable::new();
+}
+
+/// The vtable is a `static`, so its address identifies instances of `FD4Time` created in Rust.
+static FD4_TIME_VTABLE: FD4TimeVTable<FD4TimeType> = FD4TimeVTable::new();
+
+impl VTable for FD4TimeType {
+    const TABLE: &'static Self::Table = &FD4_TIME_VTABLE;
+}
+
+impl RuntimeType for FD4TimeType {
+    type Root = FD4TimeType;
+    fn is_instance(root: &FD4Time) -> bool {
+        std::ptr::eq(root.vtable, Self::TABLE)
+    }
 }
 
 pub trait FD4TimeTrait {
-    extern "C" fn destructor(&self) {}
+    /// The body of the destructor. The Rust drop glue of the object runs after it.
+    fn destructor(&self) {}
+}
+
+/// The scalar deleting destructor in the vtable of every `FD4Time` created in Rust. A panic leaks the object.
+///
+/// # Safety
+/// See `scalar_deleting_destructor`.
+pub unsafe extern "C" fn time_destructor<T: FD4TimeTrait>(this: *mut T, flags: u32) -> *mut T {
+    guard("FD4Time::destructor", this, || {
+        scalar_deleting_destructor(this, flags, |this| this.destructor())
+    });
+    forget(this);
+    this
 }
 
 impl FD4TimeTrait for FD4Time {}
 
-impl<C: VTable> FD4TimeVTable<C>
+impl<C: VTableLayout> FD4TimeVTable<C>
 where
     CppClass<C>: FD4TimeTrait,
 {
     pub const fn new() -> Self {
         Self {
-            destructor: <CppClass<C> as FD4TimeTrait>::destructor,
+            destructor: time_destructor::<CppClass<C>>,
         }
     }
 }
+
+impl<C: VTableLayout> Default for FD4TimeVTable<C>
+where
+    CppClass<C>: FD4TimeTrait,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: VTableLayout> AsRef<FD4TimeVTable<C>> for FD4TimeVTable<C> {
+    fn as_ref(&self) -> &FD4TimeVTable<C> {
+        self
+    }
+}
+
+/// Virtual calls to the `FD4Time` methods of an object, through the vtable the object actually points to.
+pub trait FD4TimeVirtual {
+    /// Destroy the object through its scalar deleting destructor.
+    ///
+    /// # Safety
+    /// See `FD4ComponentBaseVirtual::virtual_destructor`.
+    unsafe fn virtual_destructor(this: *mut Self, flags: u32);
+}
+
+impl<C: VTableLayout> FD4TimeVirtual for CppClass<C>
+where
+    C::Table: AsRef<FD4TimeVTable<C>>,
+{
+    unsafe fn virtual_destructor(this: *mut Self, flags: u32) {
+        ((*this).vtable.as_ref().destructor)(this, flags);
+    }
+}
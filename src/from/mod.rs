@@ -5,6 +5,9 @@ pub mod CS;
 #[allow(non_snake_case)]
 pub mod FD4;
 
+#[path = "dantelion2/kernel.rs"]
+#[allow(non_snake_case)]
+pub mod DLKR;
 #[path = "dantelion2/mod.rs"]
 #[allow(non_snake_case)]
 pub mod DLRF;
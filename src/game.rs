@@ -0,0 +1,18 @@
+/// The games liber-rs can be built for, one per feature of the crate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Game {
+    EldenRing,
+    Sekiro,
+    DarkSouls3,
+    ArmoredCore6,
+}
+
+/// The game the crate is built for, selected with the `eldenring`, `sekiro`, `ds3` or `ac6` feature.
+#[cfg(feature = "eldenring")]
+pub const GAME: Game = Game::EldenRing;
+#[cfg(feature = "sekiro")]
+pub const GAME: Game = Game::Sekiro;
+#[cfg(feature = "ds3")]
+pub const GAME: Game = Game::DarkSouls3;
+#[cfg(feature = "ac6")]
+pub const GAME: Game = Game::ArmoredCore6;
@@ -1,27 +1,157 @@
 use std::fmt::{Debug, Formatter};
 use std::ops::{Deref, DerefMut};
 
+// Lets the derive macros refer to this crate as `liber_rs` inside of it as well.
+extern crate self as liber_rs;
+
+#[cfg(not(any(
+    feature = "eldenring",
+    feature = "sekiro",
+    feature = "ds3",
+    feature = "ac6"
+)))]
+compile_error!("select the game with one of the `eldenring`, `sekiro`, `ds3` or `ac6` features");
+#[cfg(any(
+    all(
+        feature = "eldenring",
+        any(feature = "sekiro", feature = "ds3", feature = "ac6")
+    ),
+    all(feature = "sekiro", any(feature = "ds3", feature = "ac6")),
+    all(feature = "ds3", feature = "ac6"),
+))]
+compile_error!("only one of the `eldenring`, `sekiro`, `ds3` and `ac6` features can be enabled");
+
+mod alloc;
+mod error;
+mod firewall;
 pub mod from;
+mod game;
+mod pointer;
+mod tests;
+
+// The examples in the README are compiled as doctests.
+#[cfg(doctest)]
+#[doc = include_str!("../README.md")]
+struct ReadmeDoctests;
 
+pub use alloc::{CppAllocator, RustAllocator};
 pub use cstr::cstr;
+pub use error::*;
+pub use firewall::*;
+pub use from::details::cache::{address_cache_path, set_address_cache_path, AddressCache};
+pub use from::details::chain::*;
+pub use from::details::memory::*;
+pub use from::details::module::*;
+pub use from::details::pattern::*;
+pub use from::details::pe::{
+    DataDirectory, Export, FileVersion, ImageLayout, Import, ImportSymbol, PeImage,
+    RuntimeFunction, Section,
+};
+pub use from::details::rtti::*;
 pub use from::details::symbols::*;
-pub use widestring::widecstr;
+pub use from::details::validate::*;
+pub use from::details::version::*;
+pub use from::details::x86::*;
+pub use game::*;
+pub use inherit_macros_derive::{cpp_layout, CppLayout, Inherit};
+pub use pointer::*;
+/// `DLRuntimeClass` names are UTF-16 on every platform, like `wchar_t` on Windows.
+pub use widestring::u16cstr as widecstr;
 
-pub type DestructorFn<C> = extern "C" fn(&CppClass<C>);
+/// The MSVC scalar deleting destructor of a class.
+///
+/// Destroys the object, frees its memory if `flags` has `DELETE_FLAG` set, and returns `this`.
+pub type DestructorFn<C> =
+    unsafe extern "C" fn(this: *mut CppClass<C>, flags: u32) -> *mut CppClass<C>;
 
-pub trait VTable
+/// Set in the flags of a scalar deleting destructor when the memory of the object should be freed after
+/// it has been destroyed, like a C++ `delete` expression does.
+pub const DELETE_FLAG: u32 = 1;
+
+/// Implementation of an MSVC scalar deleting destructor for objects created in Rust.
+///
+/// Runs `destructor`, the body of the C++ destructor, followed by the Rust drop glue of the object. If `flags`
+/// has `DELETE_FLAG` set, the memory of the object is freed afterwards, with the allocator it was created in by
+/// `CppClass::new_in`, or with the Rust global allocator.
+///
+/// # Safety
+/// `this` must point to a live object that is not used after this call. If the delete flag is set, the object
+/// must have been allocated with `Box` or `CppClass::new_in`.
+pub unsafe fn scalar_deleting_destructor<T>(
+    this: *mut T,
+    flags: u32,
+    destructor: impl FnOnce(&T),
+) -> *mut T {
+    destructor(&*this);
+    std::ptr::drop_in_place(this);
+    if flags & DELETE_FLAG != 0 {
+        alloc::free(this);
+    }
+    this
+}
+
+/// The layout of the vtable of a C++ class, without an implementation of it.
+///
+/// This is all that is needed to view objects created by the game, see `CppRef` and `CppPtr`.
+pub trait VTableLayout
 where
-    <Self as VTable>::Table: 'static,
+    <Self as VTableLayout>::Table: 'static,
 {
     type Table;
+}
+
+/// A C++ class implemented in Rust, with `TABLE` as the vtable of every instance created by Rust.
+pub trait VTable: VTableLayout {
     const TABLE: &'static Self::Table;
 }
+/// Declares `B` as a base class of `Self`, so a `CppClass<Self>` can be used as a `CppClass<B>`.
+///
+/// Every class inherits itself. A class with multiple bases has a subobject with its own vtable for every base
+/// after the first one. Those are declared with a non-zero `OFFSET`, see `CppClass::with_vtable`.
+///
+/// # Safety
+/// `CppClass<Self>` must contain a `CppClass<B>` at `OFFSET` bytes, with a vtable that is laid out like
+/// `B::Table`.
+pub unsafe trait Inherits<B: VTableLayout>: VTableLayout {
+    /// Offset of the `B` subobject in a `CppClass<Self>`. This is 0 for the primary base.
+    ///
+    /// Secondary bases are fields of `Self`, so this is `CppClass::<Self>::DATA_OFFSET` plus the offset of
+    /// the field.
+    const OFFSET: usize = 0;
+}
+
+unsafe impl<C: VTableLayout> Inherits<C> for C {}
+
+/// A class whose dynamic type can be checked at runtime, which is needed for `CppClass::downcast`.
+pub trait RuntimeType: VTableLayout {
+    /// A base class of `Self` whose vtable identifies the dynamic type of an object, i.e. `FD4ComponentBaseType`,
+    /// which has `get_runtime_class`.
+    type Root: VTableLayout;
+    /// Returns `true` if `root` is part of an object whose dynamic type is exactly `Self`.
+    ///
+    /// `root` is only known to be a `Root`, so nothing but its own vtable and members may be accessed.
+    fn is_instance(root: &CppClass<Self::Root>) -> bool;
+}
+
 #[repr(C)]
-pub struct CppClass<C: VTable> {
+pub struct CppClass<C: VTableLayout> {
     pub vtable: &'static C::Table,
     data: C,
 }
 
+impl<C: VTableLayout> CppClass<C> {
+    /// Offset of the data of the class, which follows the primary vtable.
+    pub const DATA_OFFSET: usize = std::mem::offset_of!(Self, data);
+
+    /// Create an object with a vtable other than `C::TABLE`.
+    ///
+    /// This is used for the subobjects of secondary bases, which are fields of the inheriting class. Their
+    /// vtable is laid out like `C::Table`, but filled with `thunk!`s that forward to the inheriting class.
+    pub const fn with_vtable(vtable: &'static C::Table, data: C) -> Self {
+        Self { vtable, data }
+    }
+}
+
 impl<C: VTable> CppClass<C> {
     pub const fn from_data(data: C) -> Self {
         Self {
@@ -29,15 +159,241 @@ impl<C: VTable> CppClass<C> {
             data,
         }
     }
+    /// Construct an object, vtable included, in memory provided by someone else, usually the game.
+    ///
+    /// # Safety
+    /// `ptr` must be valid for writes and aligned for a `CppClass<C>`, and the object must not be used after
+    /// the memory is freed.
+    pub unsafe fn emplace<'a>(ptr: *mut Self, data: C) -> &'a mut Self {
+        ptr.write(Self::from_data(data));
+        &mut *ptr
+    }
+    /// Construct an object in memory allocated with `allocator`, like a C++ `new` expression with one of the
+    /// allocators of the game.
+    ///
+    /// The object is owned by whoever it is handed to, usually the game. It is freed with `allocator` when it
+    /// is deleted through its scalar deleting destructor, or by `CppClass::delete`.
+    pub fn new_in(data: C, allocator: &'static dyn CppAllocator) -> Result<CppPtr<C>, LiberError> {
+        let ptr = alloc::allocate::<Self>(allocator);
+        if ptr.is_null() {
+            return Err(LiberError::AllocationFailed(
+                std::alloc::Layout::new::<Self>(),
+            ));
+        }
+        Ok(CppPtr::from(unsafe {
+            Self::emplace(ptr, data) as *mut Self
+        }))
+    }
+}
+
+impl<C: VTableLayout> CppClass<C> {
+    /// Drop an object and free it with the allocator it was created in, without calling its destructor through
+    /// the vtable. Use `FD4ComponentBaseVirtual::virtual_destructor` with `DELETE_FLAG` for that.
+    ///
+    /// # Safety
+    /// `this` must point to a live object created with `CppClass::new_in` or `Box`, which is not used after
+    /// this call.
+    pub unsafe fn delete(this: *mut Self) {
+        std::ptr::drop_in_place(this);
+        alloc::free(this);
+    }
+    /// Use this object as an instance of its base class `B`.
+    ///
+    /// For secondary bases this points to the subobject of `B`, like a C++ cast adjusts `this`.
+    pub fn upcast<B: VTableLayout>(&self) -> &CppClass<B>
+    where
+        C: Inherits<B>,
+    {
+        unsafe {
+            &*(self as *const Self)
+                .byte_add(C::OFFSET)
+                .cast::<CppClass<B>>()
+        }
+    }
+    pub fn upcast_mut<B: VTableLayout>(&mut self) -> &mut CppClass<B>
+    where
+        C: Inherits<B>,
+    {
+        unsafe {
+            &mut *(self as *mut Self)
+                .byte_add(C::OFFSET)
+                .cast::<CppClass<B>>()
+        }
+    }
+    /// Use this base class subobject as the object of the subclass `T` it is a part of.
+    ///
+    /// This is how the thunks in the vtable of a secondary base get back to the full object, see `thunk!`.
+    ///
+    /// # Safety
+    /// `self` must be the `C` subobject of a `CppClass<T>`.
+    pub unsafe fn from_base_unchecked<T: Inherits<C>>(&self) -> &CppClass<T> {
+        &*(self as *const Self)
+            .byte_sub(T::OFFSET)
+            .cast::<CppClass<T>>()
+    }
+    /// # Safety
+    /// See `from_base_unchecked`.
+    pub unsafe fn from_base_unchecked_mut<T: Inherits<C>>(&mut self) -> &mut CppClass<T> {
+        &mut *(self as *mut Self)
+            .byte_sub(T::OFFSET)
+            .cast::<CppClass<T>>()
+    }
+    /// Use this object as an instance of its subclass `T`, if `T` is the dynamic type of the object.
+    ///
+    /// The dynamic type is checked through the `T::Root` subobject of `self`, so `self` is only adjusted to
+    /// the `CppClass<T>` it is part of once it is known to be one.
+    pub fn downcast<T>(&self) -> Option<&CppClass<T>>
+    where
+        T: RuntimeType + Inherits<C>,
+        C: Inherits<T::Root>,
+    {
+        if T::is_instance(self.upcast::<T::Root>()) {
+            Some(unsafe { self.from_base_unchecked::<T>() })
+        } else {
+            None
+        }
+    }
+    pub fn downcast_mut<T>(&mut self) -> Option<&mut CppClass<T>>
+    where
+        T: RuntimeType + Inherits<C>,
+        C: Inherits<T::Root>,
+    {
+        if T::is_instance(self.upcast::<T::Root>()) {
+            Some(unsafe { self.from_base_unchecked_mut::<T>() })
+        } else {
+            None
+        }
+    }
+}
+
+/// Create a function for the vtable of a secondary base `B` of `C`, which adjusts `this` back from the `B`
+/// subobject to the `CppClass<C>` it is part of before running the body. Panics are caught like in every other
+/// vtable function, see `guard`.
+///
+/// ```ignore
+/// static SECONDARY: IFooVTable<IFooType> = IFooVTable {
+///     foo: thunk!(MyClassType as IFooType, |this, value: u32| -> u32 { this.foo(value) }),
+/// };
+/// ```
+#[macro_export]
+macro_rules! thunk {
+    ($class:ty as $base:ty, |$this:ident $(, $arg:ident: $arg_ty:ty)* $(,)?| -> $ret:ty $body:block) => {{
+        extern "C" fn thunk(this: &$crate::CppClass<$base> $(, $arg: $arg_ty)*) -> $ret {
+            $crate::guard_or_abort($crate::thunk!(@name $class, $base), this, || {
+                let $this: &$crate::CppClass<$class> = unsafe { this.from_base_unchecked::<$class>() };
+                $body
+            })
+        }
+        thunk
+    }};
+    ($class:ty as $base:ty, |$this:ident $(, $arg:ident: $arg_ty:ty)* $(,)?| $body:block) => {{
+        extern "C" fn thunk(this: &$crate::CppClass<$base> $(, $arg: $arg_ty)*) {
+            $crate::guard($crate::thunk!(@name $class, $base), this, || {
+                let $this: &$crate::CppClass<$class> = unsafe { this.from_base_unchecked::<$class>() };
+                $body
+            });
+        }
+        thunk
+    }};
+    (@name $class:ty, $base:ty) => {
+        concat!("thunk of ", stringify!($class), " as ", stringify!($base))
+    };
+}
+
+/// Declare a function of the game, which is resolved the first time it is called, and then cached. The address
+/// is cast to a function pointer with the declared signature, and calls return an error if it can not be
+/// resolved.
+///
+/// The address comes from the symbol database with `symbol`, from a unique match of a pattern in `.text` with
+/// `pattern`, or from any `FnOnce() -> Result<usize, LiberError>` with `resolve`. Addresses of symbols are
+/// resolved again after `set_symbol_database` or `set_game_version` selected other symbols, the others stay
+/// cached in a `OnceLock` for the rest of the process.
+///
+/// `symbol` takes a `FunctionSymbol`, and the declared signature has to be the one of the symbol, so every
+/// declaration of it is checked at compile time. Nothing checks that signature against the function of the
+/// game though, nor the signature of functions found with `pattern` and `resolve`, so the functions have to
+/// be declared `unsafe fn`.
+///
+/// ```ignore
+/// liber_fn! {
+///     /// `CS::CSEzTask::RegisterTask`.
+///     pub unsafe fn register_task(this: *const CSEzTask, task_group: CSTaskGroup) = symbol(CSEzTaskRegisterTask);
+/// }
+///
+/// unsafe { register_task(task, CSTaskGroup::FrameBegin)? };
+/// ```
+#[macro_export]
+macro_rules! liber_fn {
+    ($(#[$attr:meta])* $vis:vis unsafe fn $name:ident($($arg:ident: $arg_ty:ty),* $(,)?) $(-> $ret:ty)? = symbol($symbol:ty) $(;)?) => {
+        $crate::liber_fn!(@fn [$(#[$attr])*] [$vis] $name($($arg: $arg_ty),*) [$($ret)?] symbol [$symbol]);
+    };
+    ($(#[$attr:meta])* $vis:vis unsafe fn $name:ident($($arg:ident: $arg_ty:ty),* $(,)?) $(-> $ret:ty)? = $kind:ident($source:expr) $(;)?) => {
+        $crate::liber_fn!(@fn [$(#[$attr])*] [$vis] $name($($arg: $arg_ty),*) [$($ret)?] $kind [$source]);
+    };
+    ($(#[$attr:meta])* $vis:vis fn $($rest:tt)*) => {
+        ::std::compile_error!("functions of the game are called without checking their signature, declare them `unsafe fn`");
+    };
+    (@fn [$($attr:tt)*] [$vis:vis] $name:ident($($arg:ident: $arg_ty:ty),*) [$($ret:ty)?] $kind:ident [$($source:tt)*]) => {
+        $($attr)*
+        ///
+        /// # Safety
+        /// The function of the game has to have this signature, and its requirements on the arguments have to be
+        /// met.
+        $vis unsafe fn $name($($arg: $arg_ty),*) -> ::std::result::Result<$crate::liber_fn!(@ret $($ret)?), $crate::LiberError> {
+            let address = $crate::liber_fn!(@address $kind [$($source)*]);
+            let function: extern "C" fn($($arg_ty),*) $(-> $ret)? =
+                ::std::mem::transmute::<usize, $crate::liber_fn!(@signature $kind [$($source)*])>(address);
+            Ok(function($($arg),*))
+        }
+    };
+    (@ret) => { () };
+    (@ret $ret:ty) => { $ret };
+    (@signature symbol [$symbol:ty]) => {
+        <$symbol as $crate::FunctionSymbol>::Fn
+    };
+    (@signature $kind:ident [$source:expr]) => {
+        _
+    };
+    (@address symbol [$symbol:ty]) => {{
+        // The address, with the generation of the symbols it was resolved with.
+        static ADDRESS: ::std::sync::RwLock<::std::option::Option<(usize, usize)>> = ::std::sync::RwLock::new(None);
+        let cached = *ADDRESS.read().unwrap_or_else(|e| e.into_inner());
+        match cached {
+            Some((generation, address)) if generation == $crate::symbols_generation() => address,
+            _ => {
+                let address = $crate::resolve_symbol(<$symbol as $crate::FunctionSymbol>::NAME)?;
+                // Resolving the first symbol detects the game version, which selects other symbols.
+                let generation = $crate::symbols_generation();
+                *ADDRESS.write().unwrap_or_else(|e| e.into_inner()) = Some((generation, address));
+                address
+            }
+        }
+    }};
+    (@address $kind:ident [$source:expr]) => {{
+        static ADDRESS: ::std::sync::OnceLock<usize> = ::std::sync::OnceLock::new();
+        match ADDRESS.get() {
+            Some(&address) => address,
+            None => {
+                let address = $crate::liber_fn!(@resolve $kind($source))?;
+                *ADDRESS.get_or_init(|| address)
+            }
+        }
+    }};
+    (@resolve pattern($signature:expr)) => {
+        $crate::Pattern::new($signature).and_then(|pattern| pattern.find_in_game(".text"))
+    };
+    (@resolve resolve($resolve:expr)) => {
+        ($resolve)()
+    };
 }
 
-impl<C: VTable + Debug> Debug for CppClass<C> {
+impl<C: VTableLayout + Debug> Debug for CppClass<C> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self.data)
     }
 }
 
-impl<C: VTable> Deref for CppClass<C> {
+impl<C: VTableLayout> Deref for CppClass<C> {
     type Target = C;
 
     fn deref(&self) -> &Self::Target {
@@ -45,7 +401,7 @@ impl<C: VTable> Deref for CppClass<C> {
     }
 }
 
-impl<C: VTable> DerefMut for CppClass<C> {
+impl<C: VTableLayout> DerefMut for CppClass<C> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.data
     }
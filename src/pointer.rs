@@ -0,0 +1,140 @@
+use crate::{CppClass, VTableLayout};
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ptr::NonNull;
+
+/// A nullable pointer to a C++ object owned by someone else, usually the game.
+///
+/// Has the same layout as a raw pointer, so it can be used for pointer fields of game structures, like
+/// `CSEzTaskProxyType::owner`.
+#[repr(transparent)]
+pub struct CppPtr<C: VTableLayout>(*mut CppClass<C>);
+
+impl<C: VTableLayout> CppPtr<C> {
+    pub const fn null() -> Self {
+        Self(std::ptr::null_mut())
+    }
+    pub const fn from_ptr(ptr: *const CppClass<C>) -> Self {
+        Self(ptr as *mut CppClass<C>)
+    }
+    pub const fn as_ptr(self) -> *mut CppClass<C> {
+        self.0
+    }
+    pub fn is_null(self) -> bool {
+        self.0.is_null()
+    }
+    /// Get a view of the object, or `None` if the pointer is null.
+    ///
+    /// # Safety
+    /// The pointer must be null or point to a live object with a vtable laid out like `C::Table`, and the
+    /// object must outlive `'a`.
+    pub unsafe fn as_ref<'a>(self) -> Option<CppRef<'a, C>> {
+        CppRef::from_ptr(self.0)
+    }
+}
+
+impl<C: VTableLayout> Clone for CppPtr<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: VTableLayout> Copy for CppPtr<C> {}
+
+impl<C: VTableLayout> Default for CppPtr<C> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<C: VTableLayout> Debug for CppPtr<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl<C: VTableLayout> From<*const CppClass<C>> for CppPtr<C> {
+    fn from(ptr: *const CppClass<C>) -> Self {
+        Self::from_ptr(ptr)
+    }
+}
+
+impl<C: VTableLayout> From<*mut CppClass<C>> for CppPtr<C> {
+    fn from(ptr: *mut CppClass<C>) -> Self {
+        Self(ptr)
+    }
+}
+
+impl<C: VTableLayout> From<&CppClass<C>> for CppPtr<C> {
+    fn from(object: &CppClass<C>) -> Self {
+        Self::from_ptr(object)
+    }
+}
+
+/// A non-null, borrowed view of a C++ object owned by someone else, usually the game.
+///
+/// Derefs to `CppClass<C>`, so fields are read the same way as for objects created in Rust. Virtual functions
+/// are called with the `*Virtual` traits, i.e. `CSEzTaskVirtual`, which go through the vtable of the object
+/// itself. None of this requires a Rust implementation of the class, only its layout.
+#[repr(transparent)]
+pub struct CppRef<'a, C: VTableLayout> {
+    ptr: NonNull<CppClass<C>>,
+    _marker: PhantomData<&'a CppClass<C>>,
+}
+
+impl<'a, C: VTableLayout> CppRef<'a, C> {
+    /// Get a view of the object at `ptr`, or `None` if `ptr` is null.
+    ///
+    /// # Safety
+    /// See `CppPtr::as_ref`.
+    pub unsafe fn from_ptr(ptr: *const CppClass<C>) -> Option<Self> {
+        NonNull::new(ptr as *mut CppClass<C>).map(|ptr| Self {
+            ptr,
+            _marker: PhantomData,
+        })
+    }
+    pub fn as_ptr(self) -> CppPtr<C> {
+        CppPtr(self.ptr.as_ptr())
+    }
+    /// Get the reference with the full lifetime of the view.
+    pub fn get(self) -> &'a CppClass<C> {
+        unsafe { self.ptr.as_ref() }
+    }
+    /// Address of the vtable the object points to. For objects created by the game this is the address of the
+    /// vtable in the game executable.
+    pub fn vtable_address(self) -> usize {
+        self.get().vtable as *const C::Table as usize
+    }
+}
+
+impl<C: VTableLayout> Clone for CppRef<'_, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: VTableLayout> Copy for CppRef<'_, C> {}
+
+impl<C: VTableLayout> Deref for CppRef<'_, C> {
+    type Target = CppClass<C>;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<C: VTableLayout> Debug for CppRef<'_, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.ptr)
+    }
+}
+
+impl<'a, C: VTableLayout> From<&'a CppClass<C>> for CppRef<'a, C> {
+    fn from(object: &'a CppClass<C>) -> Self {
+        Self {
+            ptr: NonNull::from(object),
+            _marker: PhantomData,
+        }
+    }
+}
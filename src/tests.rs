@@ -0,0 +1,648 @@
+#![cfg(test)]
+
+use crate::from::CS::{
+    CSEzTask, CSEzTaskProxy, CSEzTaskProxyType, CSEzTaskTrait, CSEzTaskType, CSEzTaskVirtual,
+    CSTaskGroup,
+};
+use crate::from::DLKR::{DLAllocator, DLAllocatorType, DLAllocatorVTable};
+use crate::from::DLRF::DLRuntimeClassVirtual;
+use crate::from::FD4::time::{FD4Time, FD4TimeType};
+use crate::from::FD4::{
+    component_destructor, DLRuntimeClassTrait, FD4ComponentBase, FD4ComponentBaseVirtual,
+    FD4TaskBase, FD4TaskBaseTrait, FD4TaskBaseType, FD4TaskBaseVirtual, FD4TaskData,
+};
+use crate::{
+    disable, forget, guard, is_disabled, liber_fn, report_error, set_error_handler,
+    set_game_version, set_module_provider, set_panic_handler, set_symbol_database, thunk,
+    CppAllocator, CppClass, CppPtr, CppRef, Inherit, Inherits, LiberError, MockModuleProvider,
+    PanicAction, RuntimeType, RustAllocator, SymbolDatabase, VTable, VTableLayout, DELETE_FLAG,
+};
+use std::alloc::Layout;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+#[repr(C)]
+#[derive(Inherit)]
+#[inherit(FD4TaskBase)]
+pub struct PanickingTaskType {
+    base: FD4TaskBaseType,
+}
+
+impl FD4TaskBaseTrait for PanickingTask {
+    fn execute(&self, _data: &FD4TaskData) {
+        panic!("disable")
+    }
+}
+
+// The panic and error handlers are global, so everything that depends on them is checked in a single test.
+#[test]
+fn panics_are_caught_in_vtable_functions() {
+    static ERRORS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    set_error_handler(|report| ERRORS.lock().unwrap().push(report.to_string()));
+    report_error("test", &LiberError::NullPointer("proxy"));
+    // Without a panic handler, the panic is reported as an error, and the task is disabled.
+    let object = 0u16;
+    assert_eq!(
+        guard("test", &object, || -> u32 { panic!("reported") }),
+        None
+    );
+    assert!(is_disabled(&object));
+    forget(&object);
+    assert_eq!(
+        *ERRORS.lock().unwrap(),
+        [
+            "test failed: proxy is null".to_string(),
+            format!("test failed: panicked on {:p}: reported", &object),
+        ]
+    );
+    // A panic in the error handler does not unwind into the function that reported the error.
+    set_error_handler(|_| panic!("in the error handler"));
+    report_error("test", &LiberError::NullPointer("proxy"));
+
+    static PANICS: AtomicUsize = AtomicUsize::new(0);
+    set_panic_handler(|report| {
+        PANICS.fetch_add(1, Ordering::SeqCst);
+        if report.message == "continue" {
+            PanicAction::Continue
+        } else {
+            PanicAction::DisableTask
+        }
+    });
+
+    let object = 0u8;
+    assert_eq!(guard("test", &object, || 1), Some(1));
+    assert_eq!(
+        guard("test", &object, || -> u32 { panic!("continue") }),
+        None
+    );
+    assert!(!is_disabled(&object));
+
+    let task = PanickingTask::new();
+    let data = FD4TaskData::new(1.0 / 60.0, 0, 0);
+    task.virtual_execute(&data);
+    assert!(is_disabled(&*task));
+    assert_eq!(PANICS.load(Ordering::SeqCst), 2);
+
+    // The task is not executed anymore.
+    task.virtual_execute(&data);
+    assert_eq!(PANICS.load(Ordering::SeqCst), 2);
+
+    forget(&*task);
+    assert!(!is_disabled(&*task));
+
+    // More objects than are checked without a lock are disabled as well.
+    let objects = [0u8; 100];
+    for object in &objects {
+        disable(object as *const u8 as usize);
+    }
+    assert!(objects.iter().all(|object| is_disabled(object)));
+    for object in &objects {
+        forget(object);
+    }
+    assert!(!objects.iter().any(|object| is_disabled(object)));
+    assert!(!is_disabled(std::ptr::null::<u8>()));
+}
+
+/// Stand-in for an allocator of the game.
+struct CountingAllocator {
+    allocated: AtomicUsize,
+    freed: AtomicUsize,
+}
+
+unsafe impl CppAllocator for CountingAllocator {
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        self.allocated.fetch_add(1, Ordering::SeqCst);
+        RustAllocator.allocate(layout)
+    }
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        self.freed.fetch_add(1, Ordering::SeqCst);
+        RustAllocator.deallocate(ptr, layout)
+    }
+}
+
+#[test]
+fn objects_are_freed_with_their_allocator() {
+    static ALLOCATOR: CountingAllocator = CountingAllocator {
+        allocated: AtomicUsize::new(0),
+        freed: AtomicUsize::new(0),
+    };
+
+    // Deleted by the game through the vtable.
+    let task = FD4TaskBase::new_in(FD4TaskBaseType::new(), &ALLOCATOR).unwrap();
+    unsafe { FD4TaskBase::virtual_destructor(task.as_ptr(), DELETE_FLAG) };
+    assert_eq!(ALLOCATOR.allocated.load(Ordering::SeqCst), 1);
+    assert_eq!(ALLOCATOR.freed.load(Ordering::SeqCst), 1);
+
+    // Deleted by Rust.
+    let task = FD4TaskBase::new_in(FD4TaskBaseType::new(), &ALLOCATOR).unwrap();
+    unsafe { CppClass::delete(task.as_ptr()) };
+    assert_eq!(ALLOCATOR.freed.load(Ordering::SeqCst), 2);
+
+    // Memory owned by someone else is not freed.
+    let mut memory = MaybeUninit::<FD4TaskBase>::uninit();
+    let task = unsafe { FD4TaskBase::emplace(memory.as_mut_ptr(), FD4TaskBaseType::new()) };
+    unsafe { FD4TaskBase::virtual_destructor(task, 0) };
+    assert_eq!(ALLOCATOR.freed.load(Ordering::SeqCst), 2);
+}
+
+/// Records the order in which the parts of an object are destroyed.
+static DESTROYED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+struct DropRecorder;
+
+impl Drop for DropRecorder {
+    fn drop(&mut self) {
+        DESTROYED.lock().unwrap().push("drop");
+    }
+}
+
+fn record_destructor(_task: &DestroyedTask) {
+    DESTROYED.lock().unwrap().push("destructor");
+}
+
+#[repr(C)]
+#[derive(Inherit)]
+#[inherit(FD4TaskBase, destructor = record_destructor)]
+pub struct DestroyedTaskType {
+    base: FD4TaskBaseType,
+    recorder: DropRecorder,
+}
+
+impl FD4TaskBaseTrait for DestroyedTask {
+    fn execute(&self, _data: &FD4TaskData) {}
+}
+
+fn destroyed_task() -> DestroyedTaskType {
+    DestroyedTaskType {
+        base: FD4TaskBaseType::new(),
+        recorder: DropRecorder,
+    }
+}
+
+#[test]
+fn scalar_deleting_destructor_runs_the_destructor_before_drop_glue() {
+    static ALLOCATOR: CountingAllocator = CountingAllocator {
+        allocated: AtomicUsize::new(0),
+        freed: AtomicUsize::new(0),
+    };
+
+    // Deleted by the game: the destructor body, then the fields, then the memory.
+    let task = CppClass::new_in(destroyed_task(), &ALLOCATOR).unwrap();
+    unsafe { CppClass::virtual_destructor(task.as_ptr(), DELETE_FLAG) };
+    assert_eq!(*DESTROYED.lock().unwrap(), ["destructor", "drop"]);
+    assert_eq!(ALLOCATOR.freed.load(Ordering::SeqCst), 1);
+
+    // Destroyed in memory owned by the game, which is not freed, and `this` is returned.
+    DESTROYED.lock().unwrap().clear();
+    let mut memory = MaybeUninit::<CppClass<DestroyedTaskType>>::uninit();
+    let task = unsafe { CppClass::emplace(memory.as_mut_ptr(), destroyed_task()) };
+    let this = task as *mut CppClass<DestroyedTaskType>;
+    let returned = unsafe { component_destructor::<DestroyedTask>(this.cast(), 0) };
+    assert_eq!(returned.cast(), this);
+    assert_eq!(*DESTROYED.lock().unwrap(), ["destructor", "drop"]);
+    assert_eq!(ALLOCATOR.freed.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn destroyed_objects_are_no_longer_disabled() {
+    // A new object at the address of a destroyed one starts out enabled.
+    let mut memory = MaybeUninit::<FD4TaskBase>::uninit();
+    let task = unsafe { FD4TaskBase::emplace(memory.as_mut_ptr(), FD4TaskBaseType::new()) };
+    disable(task as *const FD4TaskBase as usize);
+    unsafe { FD4TaskBase::virtual_destructor(task, 0) };
+    assert!(!is_disabled(memory.as_ptr()));
+
+    let mut memory = MaybeUninit::<FD4Time>::uninit();
+    let time = unsafe { FD4Time::emplace(memory.as_mut_ptr(), FD4TimeType::new(0.0)) };
+    disable(time as *const FD4Time as usize);
+    unsafe { <FD4Time as crate::from::FD4::time::FD4TimeVirtual>::virtual_destructor(time, 0) };
+    assert!(!is_disabled(memory.as_ptr()));
+}
+
+extern "C" fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+
+static RESOLVED: AtomicUsize = AtomicUsize::new(0);
+
+fn resolve_add() -> Result<usize, LiberError> {
+    RESOLVED.fetch_add(1, Ordering::SeqCst);
+    Ok(add as *const () as usize)
+}
+
+liber_fn! {
+    unsafe fn game_add(a: u32, b: u32) -> u32 = resolve(resolve_add);
+}
+
+liber_fn! {
+    unsafe fn game_missing(a: u32) = resolve(|| Err(LiberError::MissingSymbol("missing".into())));
+}
+
+#[test]
+fn game_functions_are_resolved_once() {
+    unsafe {
+        assert_eq!(game_add(1, 2), Ok(3));
+        assert_eq!(game_add(3, 4), Ok(7));
+        assert_eq!(RESOLVED.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            game_missing(1),
+            Err(LiberError::MissingSymbol("missing".into()))
+        );
+    }
+}
+
+// Two classes of the same name, which have to stay different classes.
+mod first {
+    use crate::from::FD4::{FD4TaskBaseTrait, FD4TaskBaseType, FD4TaskData};
+    use crate::Inherit;
+
+    #[repr(C)]
+    #[derive(Inherit)]
+    #[inherit(FD4TaskBase)]
+    pub struct SameNameTaskType {
+        base: FD4TaskBaseType,
+    }
+
+    impl FD4TaskBaseTrait for SameNameTask {
+        fn execute(&self, _data: &FD4TaskData) {}
+    }
+}
+
+mod second {
+    use crate::from::FD4::{FD4TaskBaseTrait, FD4TaskBaseType, FD4TaskData};
+    use crate::Inherit;
+
+    #[repr(C)]
+    #[derive(Inherit)]
+    #[inherit(FD4TaskBase)]
+    pub struct SameNameTaskType {
+        base: FD4TaskBaseType,
+        value: u64,
+    }
+
+    impl FD4TaskBaseTrait for SameNameTask {
+        fn execute(&self, _data: &FD4TaskData) {}
+    }
+}
+
+#[test]
+fn objects_are_downcast_to_their_own_class_only() {
+    let task = first::SameNameTask::new();
+    let base = task.upcast::<FD4TaskBaseType>();
+    assert!(base.downcast::<first::SameNameTaskType>().is_some());
+    assert!(base.downcast::<second::SameNameTaskType>().is_none());
+    assert!(base.downcast::<PanickingTaskType>().is_none());
+    assert!(base.downcast::<FD4TaskBaseType>().is_none());
+    assert!(std::ptr::eq(
+        base.downcast::<first::SameNameTaskType>().unwrap(),
+        &*task
+    ));
+
+    let task = FD4TaskBase::from_data(FD4TaskBaseType::new());
+    assert!(task.downcast::<FD4TaskBaseType>().is_some());
+    assert!(task.downcast::<first::SameNameTaskType>().is_none());
+}
+
+/// A secondary base class.
+#[repr(C)]
+pub struct IFooType {
+    scale: u32,
+}
+
+#[repr(C)]
+pub struct IFooVTable {
+    foo: extern "C" fn(&CppClass<IFooType>, u32) -> u32,
+}
+
+impl VTableLayout for IFooType {
+    type Table = IFooVTable;
+}
+
+extern "C" fn ifoo_foo(this: &CppClass<IFooType>, value: u32) -> u32 {
+    this.scale * value
+}
+
+static IFOO_VTABLE: IFooVTable = IFooVTable { foo: ifoo_foo };
+
+/// A class with a primary vtable and an `IFoo` subobject after its own members.
+#[repr(C)]
+pub struct TwoBaseType {
+    value: u32,
+    foo: CppClass<IFooType>,
+}
+
+#[repr(C)]
+pub struct TwoBaseVTable {
+    value: extern "C" fn(&CppClass<TwoBaseType>) -> u32,
+}
+
+impl VTableLayout for TwoBaseType {
+    type Table = TwoBaseVTable;
+}
+
+extern "C" fn two_base_value(this: &CppClass<TwoBaseType>) -> u32 {
+    this.value
+}
+
+impl VTable for TwoBaseType {
+    const TABLE: &'static TwoBaseVTable = &TwoBaseVTable {
+        value: two_base_value,
+    };
+}
+
+unsafe impl Inherits<IFooType> for TwoBaseType {
+    const OFFSET: usize =
+        CppClass::<TwoBaseType>::DATA_OFFSET + std::mem::offset_of!(TwoBaseType, foo);
+}
+
+static TWO_BASE_FOO_VTABLE: IFooVTable = IFooVTable {
+    foo: thunk!(TwoBaseType as IFooType, |this, value: u32| -> u32 {
+        this.value + value
+    }),
+};
+
+// The `IFoo` vtable of a `TwoBase` identifies it.
+impl RuntimeType for TwoBaseType {
+    type Root = IFooType;
+    fn is_instance(root: &CppClass<IFooType>) -> bool {
+        std::ptr::eq(root.vtable, &TWO_BASE_FOO_VTABLE)
+    }
+}
+
+fn two_base(value: u32) -> CppClass<TwoBaseType> {
+    CppClass::from_data(TwoBaseType {
+        value,
+        foo: CppClass::with_vtable(&TWO_BASE_FOO_VTABLE, IFooType { scale: 0 }),
+    })
+}
+
+#[test]
+fn secondary_bases_are_adjusted() {
+    let object = two_base(10);
+    let foo = object.upcast::<IFooType>();
+    assert_eq!(
+        foo as *const _ as usize - &object as *const _ as usize,
+        <TwoBaseType as Inherits<IFooType>>::OFFSET
+    );
+    assert_eq!(std::ptr::addr_of!(object.foo), foo as *const _);
+    assert_eq!((object.vtable.value)(&object), 10);
+
+    // The thunk in the vtable of the subobject gets back to the full object.
+    assert_eq!((foo.vtable.foo)(foo, 5), 15);
+    let back = foo.downcast::<TwoBaseType>().unwrap();
+    assert!(std::ptr::eq(back, &object));
+
+    // An `IFoo` that is not part of a `TwoBase` is not adjusted.
+    let foo = CppClass::with_vtable(&IFOO_VTABLE, IFooType { scale: 3 });
+    assert_eq!((foo.vtable.foo)(&foo, 5), 15);
+    assert!(foo.downcast::<TwoBaseType>().is_none());
+
+    let mut object = two_base(1);
+    object
+        .upcast_mut::<IFooType>()
+        .downcast_mut::<TwoBaseType>()
+        .unwrap()
+        .value = 2;
+    assert_eq!((object.vtable.value)(&object), 2);
+}
+
+#[repr(C)]
+#[derive(CSEzTask)]
+pub struct CountingTaskType {
+    task: CSEzTaskType,
+    executed: AtomicUsize,
+}
+
+impl CSEzTaskTrait for CountingTask {
+    fn eztask_execute(&self, _data: &FD4TaskData) {
+        self.executed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn virtual_calls_go_through_the_vtable_of_the_object() {
+    let task = CountingTask::new(AtomicUsize::new(0));
+    let data = FD4TaskData::new(1.0 / 60.0, 0, 0);
+    let base = task.upcast::<CSEzTaskType>();
+
+    // The trait method of the base class is its own, the virtual call reaches the override of the subclass.
+    base.eztask_execute(&data);
+    assert_eq!(task.executed.load(Ordering::SeqCst), 0);
+    base.virtual_eztask_execute(&data);
+    assert_eq!(task.executed.load(Ordering::SeqCst), 1);
+    // `execute` of a `CSEzTask` calls `eztask_execute`.
+    base.upcast::<FD4TaskBaseType>().virtual_execute(&data);
+    assert_eq!(task.executed.load(Ordering::SeqCst), 2);
+
+    // The proxy executes its owner through the vtable of the owner.
+    let mut proxy = CSEzTaskProxy::from_data(CSEzTaskProxyType::new(CSTaskGroup::FrameBegin));
+    proxy.set_owner(base);
+    proxy.virtual_execute(&data);
+    assert_eq!(task.executed.load(Ordering::SeqCst), 3);
+    proxy.set_owner(CppPtr::null());
+    proxy.virtual_execute(&data);
+    assert_eq!(task.executed.load(Ordering::SeqCst), 3);
+}
+
+/// A class of the game, of which only the layout is known.
+#[repr(C)]
+pub struct GameObjectType {
+    value: u32,
+}
+
+#[repr(C)]
+pub struct GameObjectVTable {
+    doubled: extern "C" fn(&CppClass<GameObjectType>) -> u32,
+}
+
+impl VTableLayout for GameObjectType {
+    type Table = GameObjectVTable;
+}
+
+extern "C" fn game_object_doubled(this: &CppClass<GameObjectType>) -> u32 {
+    this.value * 2
+}
+
+static GAME_OBJECT_VTABLE: GameObjectVTable = GameObjectVTable {
+    doubled: game_object_doubled,
+};
+
+#[test]
+fn game_objects_are_viewed_through_their_own_vtable() {
+    let object = CppClass::with_vtable(&GAME_OBJECT_VTABLE, GameObjectType { value: 21 });
+    let ptr = CppPtr::from(&object);
+    assert!(!ptr.is_null());
+    let view = unsafe { ptr.as_ref() }.unwrap();
+    assert_eq!(view.value, 21);
+    assert_eq!(
+        view.vtable_address(),
+        &GAME_OBJECT_VTABLE as *const _ as usize
+    );
+    assert_eq!((view.vtable.doubled)(&view), 42);
+    assert_eq!(view.as_ptr().as_ptr(), ptr.as_ptr());
+    assert!(std::ptr::eq(view.get(), &object));
+
+    let null = CppPtr::<GameObjectType>::default();
+    assert!(null.is_null());
+    assert!(unsafe { null.as_ref() }.is_none());
+
+    // Both fit in pointer fields of game structures.
+    assert_eq!(size_of::<CppPtr<GameObjectType>>(), size_of::<usize>());
+    assert_eq!(
+        size_of::<Option<CppRef<GameObjectType>>>(),
+        size_of::<usize>()
+    );
+
+    // The owner of a proxy is viewed the same way, and executed through its own vtable.
+    let task = CountingTask::new(AtomicUsize::new(0));
+    let mut proxy = CSEzTaskProxy::from_data(CSEzTaskProxyType::new(CSTaskGroup::FrameBegin));
+    proxy.set_owner(task.upcast::<CSEzTaskType>());
+    let owner = unsafe { proxy.owner().as_ref() }.unwrap();
+    assert!(std::ptr::eq(owner.get(), task.upcast::<CSEzTaskType>()));
+    owner.virtual_eztask_execute(&FD4TaskData::new(1.0 / 60.0, 0, 0));
+    assert_eq!(task.executed.load(Ordering::SeqCst), 1);
+}
+
+/// Held by the tests that change the module provider or the symbol database, which are global.
+pub(crate) static GAME_SYMBOLS: Mutex<()> = Mutex::new(());
+
+/// Calls of the fake `CS::CSEzTask` functions of the game, with the task they were called on, and the task
+/// group it was registered in, or `None` when it was freed.
+static TASK_CALLS: Mutex<Vec<(usize, Option<CSTaskGroup>)>> = Mutex::new(Vec::new());
+
+extern "C" fn fake_register_task(this: *const CSEzTask, task_group: CSTaskGroup) {
+    TASK_CALLS
+        .lock()
+        .unwrap()
+        .push((this as usize, Some(task_group)));
+}
+
+extern "C" fn fake_free_task(this: *const CSEzTask) {
+    TASK_CALLS.lock().unwrap().push((this as usize, None));
+}
+
+#[test]
+fn pinned_tasks_are_registered_and_freed_in_the_game() {
+    let _symbols = GAME_SYMBOLS.lock().unwrap_or_else(PoisonError::into_inner);
+    let register = fake_register_task as *const () as usize;
+    let free = fake_free_task as *const () as usize;
+    let base = register.min(free);
+    set_module_provider(MockModuleProvider::with_base(base));
+    set_symbol_database(
+        SymbolDatabase::from_toml(&format!(
+            r#"
+            [versions.fake.symbols]
+            "CS::CSEzTask::register_task" = {:#x}
+            "CS::CSEzTask::free_task" = {:#x}
+            "#,
+            register - base,
+            free - base,
+        ))
+        .unwrap(),
+    );
+    set_game_version("fake").unwrap();
+
+    // The proxy the game creates when the task is registered, which marks it as registered.
+    let proxy = CSEzTaskProxy::from_data(CSEzTaskProxyType::new(CSTaskGroup::FrameBegin));
+    let mut task = CountingTask::new(AtomicUsize::new(0));
+    unsafe {
+        task.0
+            .task
+            .set_proxy(&proxy as *const CSEzTaskProxy as *mut CSEzTaskProxy)
+    };
+    let task = task.pinned();
+    let address = &*task as *const CountingTask as usize;
+
+    // The game gets the address of the task, which stays where it is until the box is dropped.
+    assert_eq!(task.as_ref().register_task(CSTaskGroup::FrameBegin), Ok(()));
+    assert_eq!(
+        task.as_ref().register_task(CSTaskGroup::INVALID),
+        Err(LiberError::InvalidTaskGroup(CSTaskGroup::INVALID))
+    );
+    assert_eq!(
+        *TASK_CALLS.lock().unwrap(),
+        [(address, Some(CSTaskGroup::FrameBegin))]
+    );
+    drop(task);
+    assert_eq!(
+        *TASK_CALLS.lock().unwrap(),
+        [(address, Some(CSTaskGroup::FrameBegin)), (address, None)]
+    );
+
+    // A task that was never registered is not freed.
+    drop(CountingTask::new(AtomicUsize::new(0)));
+    assert_eq!(TASK_CALLS.lock().unwrap().len(), 2);
+
+    set_module_provider(MockModuleProvider::new());
+}
+
+#[test]
+fn runtime_classes_describe_their_class() {
+    let class = FD4TaskBase::runtime_class();
+    assert_eq!(
+        class.virtual_class_size(),
+        std::mem::size_of::<FD4TaskBase>()
+    );
+    assert!(!class.virtual_unk_always_false());
+
+    // Every class has ref bytes of its own, which the game can write.
+    class.virtual_ref_byte2().store(1, Ordering::SeqCst);
+    assert_eq!(class.virtual_ref_byte2().load(Ordering::SeqCst), 1);
+    assert_eq!(class.virtual_ref_byte1().load(Ordering::SeqCst), 0);
+    let other = CSEzTask::runtime_class();
+    assert_eq!(other.virtual_ref_byte2().load(Ordering::SeqCst), 0);
+    class.virtual_ref_byte2().store(0, Ordering::SeqCst);
+}
+
+static GAME_ALLOCATOR_FREED: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn game_allocate(_: &DLAllocator, _size: usize) -> *mut u8 {
+    std::ptr::null_mut()
+}
+extern "C" fn game_allocate_aligned(_: &DLAllocator, size: usize, alignment: usize) -> *mut u8 {
+    RustAllocator.allocate(Layout::from_size_align(size, alignment).unwrap())
+}
+extern "C" fn game_reallocate(_: &DLAllocator, _ptr: *mut u8, _size: usize) -> *mut u8 {
+    std::ptr::null_mut()
+}
+extern "C" fn game_reallocate_aligned(
+    _: &DLAllocator,
+    _ptr: *mut u8,
+    _size: usize,
+    _alignment: usize,
+) -> *mut u8 {
+    std::ptr::null_mut()
+}
+/// Only `FD4TaskBase`s are allocated with the allocator.
+extern "C" fn game_free(_: &DLAllocator, ptr: *mut u8) {
+    GAME_ALLOCATOR_FREED.fetch_add(1, Ordering::SeqCst);
+    unsafe { RustAllocator.deallocate(ptr, Layout::new::<FD4TaskBase>()) }
+}
+
+/// Stand-in for a `DLAllocator` of the game.
+static GAME_ALLOCATOR: DLAllocator = DLAllocator::with_vtable(
+    &DLAllocatorVTable {
+        _unk: [0; 9],
+        allocate: game_allocate,
+        allocate_aligned: game_allocate_aligned,
+        reallocate: game_reallocate,
+        reallocate_aligned: game_reallocate_aligned,
+        free: game_free,
+    },
+    DLAllocatorType,
+);
+
+#[test]
+fn free_base_frees_with_the_given_allocator() {
+    let task = FD4TaskBase::new_in(FD4TaskBaseType::new(), &GAME_ALLOCATOR).unwrap();
+    let mut base = task.as_ptr() as *mut FD4ComponentBase;
+    let class = FD4TaskBase::runtime_class();
+    unsafe { class.virtual_free_base(&mut base, &GAME_ALLOCATOR) };
+    assert!(base.is_null());
+    assert_eq!(GAME_ALLOCATOR_FREED.load(Ordering::SeqCst), 1);
+
+    // A null base is left alone.
+    unsafe { class.virtual_free_base(&mut base, &GAME_ALLOCATOR) };
+    assert_eq!(GAME_ALLOCATOR_FREED.load(Ordering::SeqCst), 1);
+}